@@ -1,187 +1,5385 @@
-use clap::{Arg, ArgAction, Command};
+//! Manage bookmarked websites and open them from a fuzzy picker.
+//!
+//! The `fzweb` binary is a thin layer over [`Config`], the bookmark store,
+//! which can also be used directly: [`Config::load`] a file, then
+//! [`add`](Config::add), [`edit`](Config::edit), [`remove`](Config::remove),
+//! [`list`](Config::list) or [`find`](Config::find) [`Website`]s. Changes are
+//! saved as they are made.
+
+use arboard::Clipboard;
+#[cfg(all(
+    unix,
+    not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))
+))]
+use arboard::SetExtLinux;
+use chrono::{DateTime, FixedOffset, Utc};
+use clap::{Arg, ArgAction, ArgMatches, Command, ValueEnum, value_parser};
+use clap_complete::{Shell, generate};
+use percent_encoding::{NON_ALPHANUMERIC, utf8_percent_encode};
+use rand::seq::IndexedRandom;
+use schemars::{JsonSchema, schema_for};
 use serde::{Deserialize, Serialize};
-use skim::prelude::{Skim, SkimItemReader, SkimItemReaderOption, SkimOptionsBuilder};
+use skim::prelude::{
+    Event, ItemPreview, PreviewContext, Skim, SkimItem, SkimItemReceiver, SkimItemSender,
+    SkimOptionsBuilder, unbounded,
+};
+use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashSet};
+use std::env;
 use std::error::Error;
+use std::fmt;
 use std::fs;
-use std::io::Cursor;
-use std::path::Path;
-
-type MyResult<T> = Result<T, Box<dyn Error>>;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use url::Url;
 
 #[derive(Debug)]
+pub enum FzwebError {
+    ConfigRead {
+        path: PathBuf,
+        source: io::Error,
+    },
+    ConfigWrite {
+        path: PathBuf,
+        source: io::Error,
+    },
+    ConfigParse {
+        path: PathBuf,
+        message: String,
+    },
+    InvalidConfig(String),
+    Serialize(String),
+    Import(serde_json::Error),
+    Bookmarks {
+        path: PathBuf,
+        message: String,
+    },
+    Io(io::Error),
+    NotFound(String),
+    Ambiguous {
+        name: String,
+        candidates: Vec<String>,
+    },
+    NoMatch(String),
+    NoTagged(Vec<String>),
+    NoTags,
+    Duplicate(String),
+    InvalidUrl(String),
+    UnsetVariable(String),
+    UnfilledPlaceholders(Vec<String>),
+    Open {
+        url: String,
+        source: io::Error,
+    },
+    NoBrowser(String),
+    EmptyField(&'static str),
+    NoWebsites,
+    EmptyCommand(String),
+    Command {
+        command: String,
+        source: io::Error,
+    },
+    PartialFailure {
+        failed: usize,
+        total: usize,
+    },
+    Clipboard(arboard::Error),
+    Http(reqwest::Error),
+    ChecksFailed(usize),
+    InvalidUrls(usize),
+    NoBackup(PathBuf),
+    Locked(PathBuf),
+    InvalidShortcut(String),
+    NoMeta(String),
+    Offline(usize),
+    SelectionAborted,
+    NotInteractive,
+    AddNeedsTerminal,
+}
+
+impl fmt::Display for FzwebError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FzwebError::ConfigRead { path, source } => {
+                write!(
+                    f,
+                    "Failed to read config file {}: {}",
+                    path.display(),
+                    source
+                )
+            }
+            FzwebError::ConfigWrite { path, source } => write!(
+                f,
+                "Failed to write {}: {}. Check that its directory is writable, or choose another file with --config.",
+                path.display(),
+                source
+            ),
+            FzwebError::ConfigParse { path, message } => {
+                write!(
+                    f,
+                    "Failed to parse config file {}: {}",
+                    path.display(),
+                    message
+                )
+            }
+            FzwebError::InvalidConfig(message) => write!(f, "Failed to parse config: {}", message),
+            FzwebError::Serialize(message) => write!(f, "Failed to serialize config: {}", message),
+            FzwebError::Import(e) => write!(f, "Failed to parse imported websites: {}", e),
+            FzwebError::Bookmarks { path, message } => write!(
+                f,
+                "Failed to import bookmarks from {}: {}",
+                path.display(),
+                message
+            ),
+            FzwebError::Io(e) => write!(f, "{}", e),
+            FzwebError::NotFound(name) => write!(f, "'{}' not found.", name),
+            FzwebError::Ambiguous { name, candidates } => write!(
+                f,
+                "'{}' could mean any of: {}.",
+                name,
+                candidates.join(", ")
+            ),
+            FzwebError::NoMatch(query) => write!(f, "No websites match '{}'.", query),
+            FzwebError::NoTagged(tags) => write!(f, "No websites tagged '{}'.", tags.join("', '")),
+            FzwebError::NoTags => write!(f, "No websites are tagged."),
+            FzwebError::Duplicate(name) => write!(f, "'{}' already exists.", name),
+            FzwebError::InvalidUrl(url) => write!(f, "'{}' is not a valid http(s) URL.", url),
+            FzwebError::UnsetVariable(name) => {
+                write!(
+                    f,
+                    "Environment variable '{}' used in the URL is not set.",
+                    name
+                )
+            }
+            FzwebError::UnfilledPlaceholders(names) => write!(
+                f,
+                "The URL needs {{{}}} filled in, which requires a terminal.",
+                names.join("}, {")
+            ),
+            FzwebError::Open { url, source } => write!(f, "Failed to open {}: {}", url, source),
+            FzwebError::NoBrowser(url) => write!(
+                f,
+                "No browser to open {} with: there is no display and $BROWSER is not set. Try --url-only or --copy instead.",
+                url
+            ),
+            FzwebError::NoWebsites => {
+                write!(f, "No websites saved; add one with --add <name> <url>.")
+            }
+            FzwebError::EmptyField(field) => write!(f, "The website {} must not be empty.", field),
+            FzwebError::EmptyCommand(name) => write!(f, "The command for '{}' is empty.", name),
+            FzwebError::Command { command, source } => {
+                write!(f, "Failed to run `{}`: {}", command, source)
+            }
+            FzwebError::PartialFailure { failed, total } => {
+                write!(f, "Failed to open {} of {} websites.", failed, total)
+            }
+            FzwebError::Clipboard(e) => write!(f, "Failed to access the clipboard: {}", e),
+            FzwebError::Http(e) => write!(f, "Failed to set up HTTP client: {}", e),
+            FzwebError::ChecksFailed(failed) => write!(f, "{} check(s) failed.", failed),
+            FzwebError::InvalidUrls(count) => {
+                write!(f, "{} website(s) have an invalid URL.", count)
+            }
+            FzwebError::NoBackup(path) => {
+                write!(f, "There is no backup to restore at {}.", path.display())
+            }
+            FzwebError::Locked(path) => write!(
+                f,
+                "The config is locked by another process ({}); try again in a moment.",
+                path.display()
+            ),
+            FzwebError::InvalidShortcut(name) => write!(
+                f,
+                "'{}' can't be a shortcut name; use letters, digits, '-' and '_' (but not http or https).",
+                name
+            ),
+            FzwebError::NoMeta(key) => write!(f, "No metadata saved under '{}'.", key),
+            FzwebError::Offline(checked) => write!(
+                f,
+                "None of the {} website(s) checked could be reached, which looks like no network rather than dead links; nothing was pruned.",
+                checked
+            ),
+            FzwebError::SelectionAborted => write!(f, "Selection aborted"),
+            FzwebError::NotInteractive => write!(
+                f,
+                "Refusing to continue without confirmation; pass --yes to skip the prompt."
+            ),
+            FzwebError::AddNeedsTerminal => write!(
+                f,
+                "`fzweb add` asks for each field on a terminal; use --add <name> <url> instead."
+            ),
+        }
+    }
+}
+
+impl FzwebError {
+    /// The process exit code for this error: 2 when the website (or a match
+    /// for the query) doesn't exist, 3 when opening it failed, 4 when the
+    /// selection was aborted and 1 for everything else. Invalid arguments
+    /// exit with 2 as well, as usual for the argument parser.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            FzwebError::NotFound(_)
+            | FzwebError::NoMatch(_)
+            | FzwebError::NoTagged(_)
+            | FzwebError::NoTags
+            | FzwebError::NoWebsites => 2,
+            FzwebError::Open { .. }
+            | FzwebError::NoBrowser(_)
+            | FzwebError::Command { .. }
+            | FzwebError::PartialFailure { .. } => 3,
+            FzwebError::SelectionAborted => 4,
+            _ => 1,
+        }
+    }
+}
+
+impl Error for FzwebError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            FzwebError::ConfigRead { source, .. }
+            | FzwebError::ConfigWrite { source, .. }
+            | FzwebError::Open { source, .. }
+            | FzwebError::Command { source, .. } => Some(source),
+            FzwebError::Import(e) => Some(e),
+            FzwebError::Io(e) => Some(e),
+            FzwebError::Clipboard(e) => Some(e),
+            FzwebError::Http(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for FzwebError {
+    fn from(e: io::Error) -> Self {
+        FzwebError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for FzwebError {
+    fn from(e: serde_json::Error) -> Self {
+        FzwebError::Serialize(e.to_string())
+    }
+}
+
+impl From<toml::ser::Error> for FzwebError {
+    fn from(e: toml::ser::Error) -> Self {
+        FzwebError::Serialize(e.to_string())
+    }
+}
+
+impl From<serde_yaml::Error> for FzwebError {
+    fn from(e: serde_yaml::Error) -> Self {
+        FzwebError::Serialize(e.to_string())
+    }
+}
+
+impl From<arboard::Error> for FzwebError {
+    fn from(e: arboard::Error) -> Self {
+        FzwebError::Clipboard(e)
+    }
+}
+
+impl From<reqwest::Error> for FzwebError {
+    fn from(e: reqwest::Error) -> Self {
+        FzwebError::Http(e)
+    }
+}
+
+type MyResult<T> = Result<T, FzwebError>;
+
+const OPEN_ALL_CONFIRM_THRESHOLD: usize = 10;
+
+const REPEAT_MAX: usize = 20;
+
+const REPEAT_CONFIRM_THRESHOLD: usize = 5;
+
+const REPEAT_DELAY: Duration = Duration::from_millis(300);
+
+const CONFIG_VERSION: u32 = 1;
+
+const HTTP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Hidden subcommand of the background fzweb that keeps a copied URL on
+/// the clipboard; see [`copy_to_clipboard`].
+const SERVE_CLIPBOARD: &str = "__serve-clipboard";
+
+#[derive(Debug, Default)]
 pub struct Args {
     open: bool,
+    open_name: Option<String>,
+    copy: bool,
+    url_only: bool,
+    action: Option<Action>,
+    browser: Option<String>,
+    private: bool,
+    new_window: bool,
+    browser_profile: Option<String>,
+    timeout: u64,
+    multi: bool,
+    query: Option<String>,
+    params: Vec<(String, String)>,
+    sort: Option<SortKey>,
+    limit: Option<usize>,
+    picker: Picker,
+    prompt: Option<String>,
+    mouse: bool,
+    all: bool,
+    random: bool,
+    least_used: bool,
+    group: Option<String>,
+    by_tag: bool,
+    create: bool,
+    peek: bool,
+    repeat: usize,
+    exact: bool,
+    list: bool,
+    archived: bool,
+    archive: Option<String>,
+    unarchive: Option<String>,
     add: Option<Vec<String>>,
+    add_from_stdin: bool,
+    add_interactive: bool,
+    edit: Option<Vec<String>>,
+    rename: Option<Vec<String>>,
     del: Option<String>,
+    del_pick: bool,
+    tags: Vec<String>,
+    desc: Option<String>,
+    aliases: Vec<String>,
+    command: Option<String>,
+    icon: Option<String>,
+    force: bool,
+    yes: bool,
+    json: bool,
+    format: Option<String>,
+    quiet: bool,
+    verbose: u8,
+    dry_run: bool,
+    config: Option<PathBuf>,
+    profile: Option<String>,
+    profiles: bool,
+    config_json: Option<String>,
+    completions: Option<Shell>,
+    export: bool,
+    import: bool,
+    import_html: Option<PathBuf>,
+    search: Option<String>,
+    count: bool,
+    stats: bool,
+    show: Option<String>,
+    note: Option<String>,
+    prune: bool,
+    fetch_favicons: bool,
+    move_to: Option<(String, usize)>,
+    sort_save: Option<SortKey>,
+    group_add: Option<(String, Vec<String>)>,
+    group_list: bool,
+    shortcut_add: Option<(String, String)>,
+    shortcut_list: bool,
+    meta_set: Option<(String, String)>,
+    meta_get: Option<String>,
+    path: bool,
+    schema: bool,
+    undo: bool,
+    doctor: bool,
+    check: bool,
+    clear: bool,
+    set_default_tag: Option<String>,
+    set_action: Option<Action>,
+    serve_clipboard: bool,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct Website {
-    name: String,
-    url: String,
+impl Args {
+    /// Whether the command is one that changes the config, which then stays
+    /// locked from loading it until the end. Opening only updates the open
+    /// count, which [`Config::record_open`] takes care of without keeping
+    /// the config locked while the picker is up.
+    fn changes_config(&self) -> bool {
+        self.add.is_some()
+            || self.add_from_stdin
+            || self.add_interactive
+            || self.edit.is_some()
+            || self.rename.is_some()
+            || self.del.is_some()
+            || self.del_pick
+            || self.archive.is_some()
+            || self.unarchive.is_some()
+            || self.note.is_some()
+            || self.import
+            || self.import_html.is_some()
+            || self.prune
+            || self.fetch_favicons
+            || self.clear
+            || self.move_to.is_some()
+            || self.sort_save.is_some()
+            || self.group_add.is_some()
+            || self.shortcut_add.is_some()
+            || self.meta_set.is_some()
+            || self.set_default_tag.is_some()
+            || self.set_action.is_some()
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct Config {
-    websites: Vec<Website>,
+/// What happens to the chosen website.
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema, ValueEnum,
+)]
+#[serde(rename_all = "lowercase")]
+enum Action {
+    #[default]
+    Open,
+    Copy,
+    #[serde(rename = "url-only")]
+    #[value(name = "url-only")]
+    Print,
 }
 
-impl Config {
-    fn load() -> Self {
-        let home_dir = dirs::home_dir().expect("Could not find config directory");
-        let config_file_path = home_dir.join(".config/fzweb/config.json");
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = self.to_possible_value().expect("no action is skipped");
+        write!(f, "{}", name.get_name())
+    }
+}
 
-        if Path::new(&config_file_path).exists() {
-            let content = fs::read_to_string(config_file_path).expect("Failed to read config file");
-            serde_json::from_str(&content).unwrap_or_else(|_| Config { websites: vec![] })
-        } else {
-            Config { websites: vec![] }
+/// Who does the choosing: the built-in skim, or an outside picker that
+/// gets the candidates from `stdout` and hands the choice back on `stdin`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Picker {
+    #[default]
+    Skim,
+    Stdout,
+    Stdin,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum SortKey {
+    Name,
+    Url,
+    Count,
+    Recent,
+    Neglected,
+}
+
+/// Orders websites by `sort`, keeping insertion order when it is `None` and
+/// among equal keys.
+fn sorted<'a>(
+    websites: impl IntoIterator<Item = &'a Website>,
+    sort: Option<SortKey>,
+) -> Vec<&'a Website> {
+    let mut websites: Vec<&Website> = websites.into_iter().collect();
+    match sort {
+        None => {}
+        Some(SortKey::Name) => websites.sort_by_key(|w| w.name.to_lowercase()),
+        Some(SortKey::Url) => websites.sort_by_key(|w| w.url.to_lowercase()),
+        Some(SortKey::Count) => {
+            if !websites.is_empty() && websites.iter().all(|w| w.open_count == 0) {
+                eprintln!("Warning: no website has been opened yet; sorting by name instead.");
+                websites.sort_by_key(|w| w.name.to_lowercase());
+            } else {
+                websites.sort_by_key(|w| Reverse(w.open_count));
+            }
         }
+        // never-opened websites last
+        Some(SortKey::Recent) => websites.sort_by_key(|w| Reverse(w.last_opened_at())),
+        // None sorts before any time, so never-opened websites come first
+        Some(SortKey::Neglected) => websites.sort_by_key(|w| (w.open_count, w.last_opened_at())),
     }
+    websites
+}
 
-    fn save(&self) {
-        let home_dir = dirs::home_dir().expect("Could not find config directory");
-        let config_file_path = home_dir.join(".config/fzweb/config.json");
-        let config_dir_path = home_dir.join(".config/fzweb");
+#[derive(Debug, Default, Clone)]
+struct OpenOptions {
+    name: Option<String>,
+    tags: Vec<String>,
+    multi: bool,
+    action: Action,
+    browser: Option<String>,
+    query: Option<String>,
+    params: Vec<(String, String)>,
+    sort: Option<SortKey>,
+    limit: Option<usize>,
+    private: bool,
+    new_window: bool,
+    browser_profile: Option<String>,
+    retry_delay: Duration,
+    all: bool,
+    random: bool,
+    least_used: bool,
+    group: Option<String>,
+    by_tag: bool,
+    create: bool,
+    peek: bool,
+    repeat: usize,
+    exact: bool,
+    yes: bool,
+    // print the candidates instead of picking (--picker stdout)
+    emit: bool,
+}
+
+/// A saved website.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Default)]
+pub struct Website {
+    /// Unique name to pick or open the website by.
+    name: String,
+    /// http(s) URL; may contain `$VARIABLES` and `{placeholders}`.
+    url: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    /// Other names the website can be opened by.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    aliases: Vec<String>,
+    /// Shell command run instead of the browser, with `{url}` replaced.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    command: Option<String>,
+    /// Emoji or short text shown before the name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    icon: Option<String>,
+    #[serde(default)]
+    open_count: u32,
+    /// RFC 3339 timestamp of the last time the website was opened.
+    #[serde(default)]
+    last_opened: Option<String>,
+    /// Free-form notes, printed when the website is opened.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    notes: Option<String>,
+    /// Path of the cached favicon.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    favicon: Option<String>,
+    /// Hidden from the picker and the list without being deleted.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    archived: bool,
+}
 
-        if !config_dir_path.exists() {
-            fs::create_dir_all(config_dir_path).expect("Failed to create config directory");
+impl Website {
+    pub fn new(name: impl Into<String>, url: impl Into<String>) -> Self {
+        Website {
+            name: name.into(),
+            url: url.into(),
+            ..Website::default()
         }
+    }
 
-        let content = serde_json::to_string_pretty(self).expect("Failed to serialize config");
-        fs::write(config_file_path, content).expect("Failed to write config file");
+    pub fn name(&self) -> &str {
+        &self.name
     }
 
-    fn add_website(&mut self, name: String, url: String) {
-        if self.websites.iter().any(|w| w.name == name) {
-            println!("Error: '{}' already exists.", name);
-            return;
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    pub fn aliases(&self) -> &[String] {
+        &self.aliases
+    }
+
+    pub fn icon(&self) -> Option<&str> {
+        self.icon.as_deref()
+    }
+
+    pub fn notes(&self) -> Option<&str> {
+        self.notes.as_deref()
+    }
+
+    pub fn is_archived(&self) -> bool {
+        self.archived
+    }
+
+    /// The cached favicon file, once `fetch-favicons` has downloaded it.
+    pub fn favicon(&self) -> Option<&str> {
+        self.favicon.as_deref()
+    }
+
+    /// Whether the name or URL contains `query`, ignoring case.
+    fn contains(&self, query: &str) -> bool {
+        let query = query.to_lowercase();
+        self.name.to_lowercase().contains(&query) || self.url.to_lowercase().contains(&query)
+    }
+
+    fn has_tags(&self, tags: &[String]) -> bool {
+        tags.iter().all(|tag| self.tags.contains(tag))
+    }
+
+    fn answers_to(&self, name: &str) -> bool {
+        self.name == name || self.aliases.iter().any(|alias| alias == name)
+    }
+
+    fn answers_to_ignoring_case(&self, name: &str) -> bool {
+        std::iter::once(&self.name)
+            .chain(&self.aliases)
+            .any(|n| n.to_lowercase() == name.to_lowercase())
+    }
+
+    /// The first of the name and aliases that one of `websites` already
+    /// answers to, ignoring case.
+    fn name_taken_in<'a>(&'a self, websites: &[Website]) -> Option<&'a String> {
+        std::iter::once(&self.name)
+            .chain(&self.aliases)
+            .find(|n| websites.iter().any(|w| w.answers_to_ignoring_case(n)))
+    }
+
+    fn last_opened_at(&self) -> Option<DateTime<FixedOffset>> {
+        DateTime::parse_from_rfc3339(self.last_opened.as_deref()?).ok()
+    }
+
+    /// How long ago the website was last opened, e.g. "2 days ago".
+    fn last_opened_ago(&self) -> Option<String> {
+        let elapsed = Utc::now().signed_duration_since(self.last_opened_at()?);
+        let (count, unit) = match elapsed.num_seconds() {
+            ..60 => return Some(String::from("just now")),
+            60..3600 => (elapsed.num_minutes(), "minute"),
+            3600..86400 => (elapsed.num_hours(), "hour"),
+            86400..2592000 => (elapsed.num_days(), "day"),
+            2592000..31536000 => (elapsed.num_days() / 30, "month"),
+            _ => (elapsed.num_days() / 365, "year"),
+        };
+        let plural = if count == 1 { "" } else { "s" };
+        Some(format!("{} {}{} ago", count, unit, plural))
+    }
+
+    /// The name as shown in the picker and list, with the icon in front and
+    /// the description after it when they are set.
+    fn label(&self) -> String {
+        let name = match &self.icon {
+            Some(icon) => format!("{} {}", icon, self.name),
+            None => self.name.clone(),
+        };
+        match &self.description {
+            Some(description) => format!("{} ({})", name, description),
+            None => name,
         }
-        self.websites.push(Website { name, url });
-        self.save();
-        println!("Added successfully!");
     }
+}
 
-    fn remove_website(&mut self, name: String) {
-        let original_len = self.websites.len();
-        self.websites.retain(|w| w.name != name);
-        if self.websites.len() < original_len {
-            self.save();
-            println!("Deleted '{}'.", name);
-        } else {
-            println!("Error: '{}' not found.", name);
+impl fmt::Display for Website {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "name: {}", self.name)?;
+        writeln!(f, "url: {}", self.url)?;
+        writeln!(
+            f,
+            "description: {}",
+            self.description.as_deref().unwrap_or("")
+        )?;
+        writeln!(f, "tags: {}", self.tags.join(", "))?;
+        writeln!(f, "aliases: {}", self.aliases.join(", "))?;
+        writeln!(f, "icon: {}", self.icon.as_deref().unwrap_or(""))?;
+        writeln!(f, "open count: {}", self.open_count)?;
+        match (self.last_opened_ago(), &self.last_opened) {
+            (Some(ago), Some(timestamp)) => write!(f, "last opened: {} ({})", ago, timestamp)?,
+            (None, Some(timestamp)) => write!(f, "last opened: {}", timestamp)?,
+            (_, None) => write!(f, "last opened: never")?,
+        }
+        if self.archived {
+            write!(f, "\narchived: yes")?;
         }
+        if let Some(favicon) = &self.favicon {
+            write!(f, "\nfavicon: {}", favicon)?;
+        }
+        if let Some(notes) = &self.notes {
+            write!(f, "\nnotes:\n{}", notes)?;
+        }
+        Ok(())
     }
+}
 
-    fn open_website(&self) {
-        let names = self
-            .websites
-            .iter()
-            .map(|website| website.name.clone())
-            .collect();
+#[derive(Debug, Serialize)]
+#[serde(tag = "action", rename_all = "lowercase")]
+enum Outcome {
+    Added {
+        name: String,
+        url: String,
+    },
+    Updated {
+        name: String,
+        url: String,
+    },
+    Renamed {
+        from: String,
+        to: String,
+    },
+    Moved {
+        name: String,
+        position: usize,
+    },
+    Deleted {
+        name: String,
+    },
+    Cancelled {
+        name: String,
+    },
+    Imported {
+        added: usize,
+        skipped: usize,
+    },
+    Pruned {
+        names: Vec<String>,
+    },
+    Cleared {
+        removed: usize,
+    },
+    Grouped {
+        group: String,
+        names: Vec<String>,
+    },
+    #[serde(rename = "default-tag")]
+    DefaultTagSet {
+        tag: Option<String>,
+    },
+    #[serde(rename = "default-action")]
+    DefaultActionSet {
+        default_action: Action,
+    },
+    Noted {
+        name: String,
+        notes: Option<String>,
+    },
+    Sorted {
+        by: String,
+        count: usize,
+    },
+    Fetched {
+        fetched: usize,
+        skipped: usize,
+        failed: usize,
+    },
+    Archived {
+        name: String,
+    },
+    Restored {
+        path: PathBuf,
+    },
+    #[serde(rename = "shortcut")]
+    ShortcutSet {
+        name: String,
+        url: String,
+    },
+    Unarchived {
+        name: String,
+    },
+    #[serde(rename = "meta")]
+    MetaSet {
+        key: String,
+        value: Option<String>,
+    },
+}
 
-        if let Ok(name) = select(names) {
-            println!("{} is selected", name);
-            if let Some(website) = self.websites.iter().find(|w| w.name == name) {
-                if let Err(e) = open::that(&website.url) {
-                    eprintln!("Failed to open URL: {}", e);
-                    std::process::exit(1);
-                }
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Outcome::Added { .. } => write!(f, "Added successfully!"),
+            Outcome::Updated { name, .. } => write!(f, "Updated '{}'.", name),
+            Outcome::Renamed { from, to } => write!(f, "Renamed '{}' to '{}'.", from, to),
+            Outcome::Moved { name, position } => {
+                write!(f, "Moved '{}' to position {}.", name, position)
+            }
+            Outcome::Deleted { name } => write!(f, "Deleted '{}'.", name),
+            Outcome::Cancelled { .. } => write!(f, "Cancelled."),
+            Outcome::Imported { added, skipped } => write!(
+                f,
+                "Imported {} website(s), skipped {} duplicate(s).",
+                added, skipped
+            ),
+            Outcome::Grouped { group, names } => {
+                write!(f, "Added {} website(s) to group '{}'.", names.len(), group)
+            }
+            Outcome::DefaultTagSet { tag: Some(tag) } => write!(f, "Default tag set to '{}'.", tag),
+            Outcome::DefaultTagSet { tag: None } => write!(f, "Default tag cleared."),
+            Outcome::DefaultActionSet { default_action } => {
+                write!(f, "Default action set to {}.", default_action)
+            }
+            Outcome::Cleared { removed } => write!(f, "Deleted all {} websites.", removed),
+            Outcome::Pruned { names } if names.is_empty() => write!(f, "Nothing to prune."),
+            Outcome::Pruned { names } => write!(f, "Pruned {} website(s).", names.len()),
+            Outcome::Noted {
+                name,
+                notes: Some(_),
+            } => write!(f, "Saved the notes for '{}'.", name),
+            Outcome::Noted { name, notes: None } => write!(f, "Removed the notes for '{}'.", name),
+            Outcome::Sorted { by, count } => write!(f, "Sorted {} website(s) by {}.", count, by),
+            Outcome::Restored { path } => write!(
+                f,
+                "Restored {} from its backup; run undo again to redo.",
+                path.display()
+            ),
+            Outcome::MetaSet {
+                key,
+                value: Some(value),
+            } => write!(f, "Set '{}' to '{}'.", key, value),
+            Outcome::MetaSet { key, value: None } => write!(f, "Removed '{}'.", key),
+            Outcome::ShortcutSet { name, url } => {
+                write!(f, "Shortcut '{}:' now expands to {}.", name, url)
             }
+            Outcome::Archived { name } => write!(f, "Archived '{}'.", name),
+            Outcome::Unarchived { name } => write!(f, "Unarchived '{}'.", name),
+            Outcome::Fetched {
+                fetched,
+                skipped,
+                failed,
+            } => write!(
+                f,
+                "Fetched {} favicon(s), {} already cached, {} failed.",
+                fetched, skipped, failed
+            ),
         }
     }
 }
 
-fn cli() -> Command {
-    Command::new("fzweb")
-        .about("A CLI tool to manage and open websites interactively.")
-        .arg(
-            Arg::new("add")
-                .long("add")
-                .short('a')
-                .value_names(vec!["name", "url"])
-                .num_args(2)
-                .action(ArgAction::Append)
-                .help("Add a website with a name and URL"),
-        )
-        .arg(
-            Arg::new("open")
-                .long("open")
-                .short('o')
-                .action(ArgAction::SetTrue)
-                .help("Open a website in your default browser"),
-        )
-        .arg(
-            Arg::new("del")
-                .long("del")
-                .short('d')
-                .action(ArgAction::Set)
-                .num_args(1)
-                .value_name("name")
-                .help("Delete a website by name"),
-        )
+/// How outcomes are reported, from the global output flags.
+#[derive(Debug, Default, Clone, Copy)]
+struct Report {
+    json: bool,
+    dry_run: bool,
+    quiet: bool,
+    verbose: u8,
 }
 
-pub fn get_args() -> MyResult<Args> {
-    let matches = cli().get_matches();
+impl Report {
+    /// Prints a diagnostic line to stderr when `-v` was given at least
+    /// `level` times.
+    fn log(self, level: u8, message: impl fmt::Display) {
+        if self.verbose >= level {
+            eprintln!("[fzweb] {}", message);
+        }
+    }
+}
 
-    Ok(Args {
-        add: matches
-            .get_many::<String>("add")
-            .map(|s| s.map(ToString::to_string).collect()),
-        del: matches.get_one::<String>("del").cloned(),
-        open: matches.get_flag("open"),
-    })
+impl Outcome {
+    /// Prints the outcome; with `dry_run` it is phrased as what would have
+    /// happened and the JSON form gains `"dry_run": true`. `quiet` silences
+    /// everything but JSON.
+    fn report(&self, report: Report) -> MyResult<()> {
+        if report.quiet && !report.json {
+            return Ok(());
+        }
+        match (report.json, report.dry_run) {
+            (true, false) => println!("{}", serde_json::to_string(self)?),
+            (true, true) => {
+                let mut value = serde_json::to_value(self)?;
+                value["dry_run"] = serde_json::Value::Bool(true);
+                println!("{}", value);
+            }
+            (false, false) => println!("{}", self),
+            (false, true) => println!("{}", self.hypothetical()),
+        }
+        Ok(())
+    }
+
+    fn hypothetical(&self) -> String {
+        match self {
+            Outcome::Added { name, .. } => format!("Would add '{}'.", name),
+            Outcome::Updated { name, .. } => format!("Would update '{}'.", name),
+            Outcome::Renamed { from, to } => format!("Would rename '{}' to '{}'.", from, to),
+            Outcome::Moved { name, position } => {
+                format!("Would move '{}' to position {}.", name, position)
+            }
+            Outcome::Deleted { name } => format!("Would delete '{}'.", name),
+            Outcome::Imported { added, skipped } => format!(
+                "Would import {} website(s), skipping {} duplicate(s).",
+                added, skipped
+            ),
+            Outcome::Grouped { group, names } => {
+                format!("Would add {} website(s) to group '{}'.", names.len(), group)
+            }
+            Outcome::DefaultTagSet { tag: Some(tag) } => {
+                format!("Would set the default tag to '{}'.", tag)
+            }
+            Outcome::DefaultTagSet { tag: None } => String::from("Would clear the default tag."),
+            Outcome::DefaultActionSet { default_action } => {
+                format!("Would set the default action to {}.", default_action)
+            }
+            Outcome::Cleared { removed } => format!("Would delete all {} websites.", removed),
+            Outcome::Pruned { names } if !names.is_empty() => {
+                format!("Would prune {} website(s).", names.len())
+            }
+            Outcome::Noted {
+                name,
+                notes: Some(_),
+            } => {
+                format!("Would save the notes for '{}'.", name)
+            }
+            Outcome::Noted { name, notes: None } => {
+                format!("Would remove the notes for '{}'.", name)
+            }
+            Outcome::Sorted { by, count } => {
+                format!("Would sort {} website(s) by {}.", count, by)
+            }
+            Outcome::Fetched {
+                fetched, skipped, ..
+            } => format!(
+                "Would fetch {} favicon(s), {} already cached.",
+                fetched, skipped
+            ),
+            Outcome::Restored { path } => {
+                format!("Would restore {} from its backup.", path.display())
+            }
+            Outcome::ShortcutSet { name, url } => {
+                format!("Would make '{}:' expand to {}.", name, url)
+            }
+            Outcome::MetaSet {
+                key,
+                value: Some(value),
+            } => format!("Would set '{}' to '{}'.", key, value),
+            Outcome::MetaSet { key, value: None } => format!("Would remove '{}'.", key),
+            Outcome::Archived { name } => format!("Would archive '{}'.", name),
+            Outcome::Unarchived { name } => format!("Would unarchive '{}'.", name),
+            Outcome::Cancelled { .. } | Outcome::Pruned { .. } => self.to_string(),
+        }
+    }
 }
 
-fn select(names: Vec<String>) -> MyResult<String> {
-    let options = SkimOptionsBuilder::default()
-        .height(String::from("100%"))
-        .no_multi(true)
-        .no_mouse(true)
-        .build()
-        .unwrap();
+#[derive(Debug, Serialize)]
+struct Stats<'a> {
+    total: usize,
+    tags: BTreeMap<&'a str, usize>,
+    most_opened: Vec<(&'a str, u32)>,
+}
 
-    let input = names.join("\n");
+impl fmt::Display for Stats<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Total: {}", self.total)?;
+        if !self.tags.is_empty() {
+            write!(f, "\nTags:")?;
+            for (tag, count) in &self.tags {
+                write!(f, "\n  {}: {}", tag, count)?;
+            }
+        }
+        if !self.most_opened.is_empty() {
+            write!(f, "\nMost opened:")?;
+            for (name, count) in &self.most_opened {
+                write!(f, "\n  {}: {}", name, count)?;
+            }
+        }
+        Ok(())
+    }
+}
 
-    let item_reader = SkimItemReader::new(SkimItemReaderOption::default());
-    let items = item_reader.of_bufread(Cursor::new(input));
-    let output = Skim::run_with(&options, Some(items)).ok_or("Selection aborted")?;
+/// How the picker looks and behaves.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+struct SkimConfig {
+    /// Height of the picker, in lines or as a percentage of the terminal.
+    height: String,
+    /// Show the list top-down with the prompt at the top.
+    reverse: bool,
+    prompt: String,
+    /// Let the picker be used with the mouse.
+    mouse: bool,
+}
 
-    if output.is_abort {
-        return Err("Selection aborted".into());
+impl Default for SkimConfig {
+    fn default() -> Self {
+        SkimConfig {
+            height: String::from("100%"),
+            reverse: false,
+            prompt: String::from("> "),
+            mouse: false,
+        }
     }
-    output
-        .selected_items
-        .first()
-        .map(|s| s.output().to_string())
-        .ok_or_else(|| "No selection made".into())
 }
 
-pub fn run(args: Args) -> MyResult<()> {
-    let mut config = Config::load();
+/// Parses a JSON config that isn't tied to a file; saving it does nothing.
+impl FromStr for Config {
+    type Err = FzwebError;
 
-    // init
-    if config.websites.is_empty() {
-        config.save();
+    fn from_str(s: &str) -> MyResult<Self> {
+        Config::from_json(s).map_err(|e| FzwebError::InvalidConfig(e.to_string()))
     }
+}
 
-    // add
-    if let Some(add_site_info) = args.add {
-        if let (Some(name), Some(url)) = (add_site_info.get(0), add_site_info.get(1)) {
-            config.add_website(name.clone(), url.clone());
+/// The file format of a config, chosen by its extension. Anything that
+/// isn't TOML or YAML is read and written as JSON.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl Format {
+    fn of(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => Format::Toml,
+            Some("yaml" | "yml") => Format::Yaml,
+            _ => Format::Json,
         }
     }
+}
 
-    // del
-    if let Some(delete_site_info) = args.del {
-        config.remove_website(delete_site_info);
+/// The fzweb config file.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct Config {
+    /// Layout version of the file. Files written before versioning was
+    /// introduced have none and are treated as version 0.
+    #[serde(default)]
+    version: u32,
+    websites: Vec<Website>,
+    #[serde(default)]
+    skim: SkimConfig,
+    /// Group name -> names of the websites in it.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    groups: BTreeMap<String, Vec<String>>,
+    /// The picker only offers websites with this tag unless --tag or --all
+    /// says otherwise.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    default_tag: Option<String>,
+    /// What opening a website does when neither --copy, --url-only nor
+    /// --action is given; `open` when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    default_action: Option<Action>,
+    /// Shell command run after each website is opened, with `FZWEB_NAME`
+    /// and `FZWEB_URL` set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    on_open: Option<String>,
+    /// Shortcut name -> URL prefix; a website URL `gh:rust-lang/rust` opens
+    /// as the `gh` prefix followed by `rust-lang/rust`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    shortcuts: BTreeMap<String, String>,
+    /// Free-form notes about the config itself, such as where it was
+    /// exported from; fzweb never reads them.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    metadata: BTreeMap<String, String>,
+    #[serde(skip)]
+    path: PathBuf,
+    // with --dry-run, reports what would change but never touches the disk
+    #[serde(skip)]
+    report: Report,
+    // picker settings changed by --prompt or --mouse, for this run only
+    #[serde(skip)]
+    cli_skim: Option<SkimConfig>,
+    // held from loading until the config is dropped, see Config::load_locked
+    #[serde(skip)]
+    lock: Option<ConfigLock>,
+}
+
+impl Config {
+    /// The picker settings in effect: the config's, unless the command line
+    /// overrode some of them.
+    fn skim(&self) -> &SkimConfig {
+        self.cli_skim.as_ref().unwrap_or(&self.skim)
     }
 
-    // open
-    if args.open {
-        config.open_website();
+    /// Resolves which config file to use: an explicit `config` path wins,
+    /// then the named `profile`, then [`Config::default_path`].
+    pub fn resolve_path(config: Option<&Path>, profile: Option<&str>) -> PathBuf {
+        match (config, profile) {
+            (Some(path), _) => path.to_path_buf(),
+            (None, Some(profile)) => Self::profile_path(profile),
+            (None, None) => Self::default_path(),
+        }
     }
 
-    Ok(())
+    /// Resolves the config file location used when neither `--config` nor
+    /// `--profile` is given.
+    ///
+    /// `FZWEB_CONFIG` wins when set. Otherwise `~/.config/fzweb/config.json`
+    /// is used, falling back to `config.toml`, `config.yaml` or `config.yml`
+    /// in the same directory only when the JSON file does not exist and one
+    /// of those does.
+    pub fn default_path() -> PathBuf {
+        if let Some(path) = env::var_os("FZWEB_CONFIG") {
+            return PathBuf::from(path);
+        }
+        Self::profile_path("config")
+    }
+
+    /// The directory holding the default config and every profile. Without a
+    /// home directory this is relative to the working directory, so a
+    /// missing `$HOME` surfaces as a read or write error naming the path.
+    fn dir() -> PathBuf {
+        dirs::home_dir().unwrap_or_default().join(".config/fzweb")
+    }
+
+    /// `~/.config/fzweb/<profile>.json`, or the `.toml`, `.yaml` or `.yml`
+    /// file of the same name when the JSON one doesn't exist but that does.
+    fn profile_path(profile: &str) -> PathBuf {
+        let config_dir_path = Self::dir();
+        let json_file_path = config_dir_path.join(format!("{}.json", profile));
+        if json_file_path.exists() {
+            return json_file_path;
+        }
+        ["toml", "yaml", "yml"]
+            .iter()
+            .map(|ext| config_dir_path.join(format!("{}.{}", profile, ext)))
+            .find(|path| path.exists())
+            .unwrap_or(json_file_path)
+    }
+
+    /// Names of the profiles found in the config directory, sorted.
+    fn profiles() -> MyResult<Vec<String>> {
+        let entries = match fs::read_dir(Self::dir()) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut profiles = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if path
+                .extension()
+                .is_some_and(|ext| ["json", "toml", "yaml", "yml"].iter().any(|e| ext == *e))
+                && let Some(stem) = path.file_stem()
+            {
+                profiles.push(stem.to_string_lossy().into_owned());
+            }
+        }
+        profiles.sort();
+        profiles.dedup();
+        Ok(profiles)
+    }
+
+    /// Reads the config at `path`, or starts an empty one if it doesn't exist.
+    pub fn load(path: &Path) -> MyResult<Self> {
+        let config_file_path = path.to_path_buf();
+
+        // a file that exists but can't be looked at must not pass for a
+        // missing one, or the next save would replace it
+        let missing = match fs::metadata(&config_file_path) {
+            Ok(_) => false,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => true,
+            Err(source) => {
+                return Err(FzwebError::ConfigRead {
+                    path: config_file_path,
+                    source,
+                });
+            }
+        };
+        if missing {
+            return Ok(Config {
+                version: CONFIG_VERSION,
+                websites: vec![],
+                skim: SkimConfig::default(),
+                groups: BTreeMap::new(),
+                default_tag: None,
+                default_action: None,
+                on_open: None,
+                shortcuts: BTreeMap::new(),
+                metadata: BTreeMap::new(),
+                path: config_file_path,
+                report: Report::default(),
+                cli_skim: None,
+                lock: None,
+            });
+        }
+
+        let content =
+            fs::read_to_string(&config_file_path).map_err(|source| FzwebError::ConfigRead {
+                path: config_file_path.clone(),
+                source,
+            })?;
+        let mut config: Config = match Format::of(&config_file_path) {
+            Format::Json => Config::from_json(&content).map_err(|e| e.to_string()),
+            Format::Toml => toml::from_str(&content).map_err(|e| e.to_string()),
+            Format::Yaml => serde_yaml::from_str(&content).map_err(|e| e.to_string()),
+        }
+        .map_err(|message| FzwebError::ConfigParse {
+            path: config_file_path.clone(),
+            message,
+        })?;
+        config.path = config_file_path;
+        Ok(config)
+    }
+
+    fn from_json(s: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(s)
+    }
+
+    /// Like [`Config::load`], but takes the config's lock first and holds it
+    /// until the config is dropped, so no other fzweb can save the file
+    /// between this read and the writes that follow it.
+    pub fn load_locked(path: &Path) -> MyResult<Self> {
+        create_parent_dir(path)?;
+        let lock = ConfigLock::take(path)?;
+        let mut config = Self::load(path)?;
+        config.lock = Some(lock);
+        Ok(config)
+    }
+
+    /// Upgrades a config written by an older release to the current layout
+    /// and saves it. Each step moves the config up by exactly one version.
+    fn migrate(&mut self) -> MyResult<()> {
+        if self.version >= CONFIG_VERSION {
+            return Ok(());
+        }
+        while self.version < CONFIG_VERSION {
+            match self.version {
+                // 0 -> 1: only the version field itself was added
+                0 => {}
+                _ => unreachable!("no migration from config version {}", self.version),
+            }
+            self.version += 1;
+        }
+        self.backup()?;
+        self.save()
+    }
+
+    /// Drops websites whose name repeats an earlier one, as hand-edited files
+    /// can contain, keeping the first. Names differing only in case are kept,
+    /// and looking them up that way reports them as ambiguous. The file is
+    /// backed up and re-saved if anything was removed.
+    fn dedupe(&mut self) -> MyResult<()> {
+        let before = self.websites.len();
+        let mut seen = HashSet::new();
+        self.websites.retain(|w| seen.insert(w.name.clone()));
+        let removed = before - self.websites.len();
+        if removed > 0 {
+            eprintln!(
+                "Note: removed {} duplicate website(s) from {}.",
+                removed,
+                self.path.display()
+            );
+            self.backup()?;
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    /// Copies the config file as it currently is on disk to `<file>.bak`.
+    fn backup(&self) -> MyResult<()> {
+        if !self.report.dry_run && self.path.is_file() {
+            let backup = with_suffix(&self.path, ".bak");
+            fs::copy(&self.path, &backup).map_err(write_error(&backup))?;
+        }
+        Ok(())
+    }
+
+    /// Swaps the config at `path` with its `.bak` file, after asking, so the
+    /// last backed-up change is reverted and running it again redoes it.
+    /// Works on the files alone, so a config that no longer parses can be
+    /// restored too.
+    fn undo(path: &Path, yes: bool, dry_run: bool) -> MyResult<Outcome> {
+        let backup = with_suffix(path, ".bak");
+        if !backup.is_file() {
+            return Err(FzwebError::NoBackup(backup));
+        }
+        let outcome = Outcome::Restored {
+            path: path.to_path_buf(),
+        };
+        if dry_run {
+            return Ok(outcome);
+        }
+        if !yes && !confirm(&format!("Replace {} with its backup?", path.display()))? {
+            return Ok(Outcome::Cancelled {
+                name: path.display().to_string(),
+            });
+        }
+        let _lock = ConfigLock::take(path)?;
+        if path.exists() {
+            let swap = with_suffix(path, ".undo");
+            fs::rename(path, &swap)?;
+            fs::rename(&backup, path)?;
+            fs::rename(&swap, &backup)?;
+        } else {
+            fs::rename(&backup, path)?;
+        }
+        Ok(outcome)
+    }
+
+    /// The config as pretty-printed JSON, the way it is saved to `.json`
+    /// files.
+    pub fn to_json_string(&self) -> String {
+        serde_json::to_string_pretty(self).expect("a config always serializes to JSON")
+    }
+
+    /// Writes the config to its file. Configs without one, such as those
+    /// given with `--config-json`, live only in memory. The config's lock
+    /// is taken for the write unless it has been held since loading.
+    fn save(&self) -> MyResult<()> {
+        if self.report.dry_run || self.path.as_os_str().is_empty() {
+            return Ok(());
+        }
+        let config_file_path = &self.path;
+
+        create_parent_dir(config_file_path)?;
+
+        let _lock = match self.lock {
+            Some(_) => None,
+            None => Some(ConfigLock::take(config_file_path)?),
+        };
+
+        let content = match Format::of(config_file_path) {
+            Format::Json => self.to_json_string(),
+            Format::Toml => toml::to_string_pretty(self)?,
+            Format::Yaml => serde_yaml::to_string(self)?,
+        };
+
+        // write next to the real file and rename over it so an interrupted
+        // write never leaves a truncated config behind
+        let tmp_file_path = with_suffix(config_file_path, ".tmp");
+
+        fs::write(&tmp_file_path, content).map_err(write_error(&tmp_file_path))?;
+        if let Err(e) = fs::rename(&tmp_file_path, config_file_path) {
+            let _ = fs::remove_file(&tmp_file_path);
+            return Err(write_error(config_file_path)(e));
+        }
+        Ok(())
+    }
+
+    /// Validates and saves a new website. Its name and URL are trimmed and
+    /// must not be empty, its name and aliases must not be taken, and its
+    /// URL is stored normalized: `https://` is prepended if it has no scheme
+    /// and the host is lowercased.
+    pub fn add(&mut self, website: Website) -> MyResult<()> {
+        let website = self.check_new(website)?;
+        self.websites.push(website);
+        self.save()
+    }
+
+    /// Runs the checks of [`Config::add`] on a website about to be added
+    /// and returns it trimmed and normalized, without adding it.
+    fn check_new(&self, mut website: Website) -> MyResult<Website> {
+        website.name = website.name.trim().to_string();
+        website.url = website.url.trim().to_string();
+        if website.name.is_empty() {
+            return Err(FzwebError::EmptyField("name"));
+        }
+        if website.url.is_empty() {
+            return Err(FzwebError::EmptyField("URL"));
+        }
+        if website
+            .command
+            .as_deref()
+            .is_some_and(|c| c.trim().is_empty())
+        {
+            return Err(FzwebError::EmptyCommand(website.name));
+        }
+        if let Some(taken) = website.name_taken_in(&self.websites) {
+            return Err(FzwebError::Duplicate(taken.clone()));
+        }
+        website.url = self.validate_url(&website.url)?;
+        Ok(website)
+    }
+
+    fn add_website(&mut self, website: Website, force: bool) -> MyResult<Outcome> {
+        self.add(website)?;
+        let added = &self.websites[self.websites.len() - 1];
+        if let Some(warning) = self.duplicate_url_warning(added, force) {
+            eprintln!("{}", warning);
+        }
+        let (name, url) = (added.name.clone(), added.url.clone());
+        Ok(Outcome::Added { name, url })
+    }
+
+    /// The note printed when another website already has the URL of
+    /// `website`, unless `force` asks for the duplicate.
+    fn duplicate_url_warning(&self, website: &Website, force: bool) -> Option<String> {
+        if force {
+            return None;
+        }
+        let key = |url: &str| canonical_key(&expand_shortcut(&self.shortcuts, url));
+        let other = self
+            .websites
+            .iter()
+            .find(|w| w.name != website.name && key(&w.url) == key(&website.url))?;
+        Some(format!(
+            "Note: this URL is already saved as '{}'.",
+            other.name
+        ))
+    }
+
+    /// Asks for a name, URL, tags and description on the terminal and adds
+    /// the website. An empty name or URL, or end of input, cancels.
+    fn add_interactively(&mut self, force: bool) -> MyResult<Outcome> {
+        if !io::stdin().is_terminal() {
+            return Err(FzwebError::AddNeedsTerminal);
+        }
+        let name = ask("Name:")?;
+        if name.is_empty() {
+            return Ok(Outcome::Cancelled { name });
+        }
+        let url = ask("URL:")?;
+        if url.is_empty() {
+            return Ok(Outcome::Cancelled { name });
+        }
+        let tags = ask("Tags (comma-separated):")?
+            .split(',')
+            .map(str::trim)
+            .filter(|tag| !tag.is_empty())
+            .map(ToString::to_string)
+            .collect();
+        let description = Some(ask("Description:")?).filter(|d| !d.is_empty());
+        let website = Website {
+            name,
+            url,
+            tags,
+            description,
+            ..Website::default()
+        };
+        self.add_website(website, force)
+    }
+
+    /// Adds one website per `name<TAB>url` or `name,url` line, reading every
+    /// line before adding anything and saving once like an import. Malformed
+    /// lines and invalid URLs are reported with their line number and skipped.
+    fn add_from_reader(&mut self, reader: impl BufRead, force: bool) -> MyResult<Outcome> {
+        let mut websites = Vec::new();
+        for (index, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let Some((name, url)) = line.split_once('\t').or_else(|| line.split_once(',')) else {
+                eprintln!("line {}: expected 'name<TAB>url' or 'name,url'", index + 1);
+                continue;
+            };
+            let website = Website::new(name, url);
+            websites.push((format!("line {}", index + 1), website));
+        }
+        let before = self.websites.len();
+        let outcome = self.merge_websites(websites)?;
+        for added in &self.websites[before..] {
+            if let Some(warning) = self.duplicate_url_warning(added, force) {
+                eprintln!("{}", warning);
+            }
+        }
+        Ok(outcome)
+    }
+
+    /// Checks a URL about to be saved and returns it normalized. A URL
+    /// using a shortcut is checked in its expanded form but kept as typed.
+    fn validate_url(&self, url: &str) -> MyResult<String> {
+        let invalid = || FzwebError::InvalidUrl(url.to_string());
+        match expand_shortcut(&self.shortcuts, url) {
+            Cow::Owned(expanded) => validate_url(&expanded)
+                .map(|_| url.trim().to_string())
+                .ok_or_else(invalid),
+            Cow::Borrowed(_) => validate_url(url).ok_or_else(invalid),
+        }
+    }
+
+    /// Points the website named `name` at a new URL, checked and normalized
+    /// like in [`Config::add`].
+    pub fn edit(&mut self, name: &str, url: &str) -> MyResult<()> {
+        let url = self.validate_url(url)?;
+        let index = self.resolve(name)?;
+        self.websites[index].url = url;
+        self.save()
+    }
+
+    fn edit_website(&mut self, name: String, url: String) -> MyResult<Outcome> {
+        let index = self.resolve(&name)?;
+        let name = self.websites[index].name.clone();
+        self.edit(&name, &url)?;
+        let url = self.websites[index].url.clone();
+        Ok(Outcome::Updated { name, url })
+    }
+
+    /// Opens the notes of the website named `name` in the user's editor and
+    /// saves what is left when it exits. Emptying the file removes them.
+    fn edit_notes(&mut self, name: &str) -> MyResult<Outcome> {
+        let index = self.resolve(name)?;
+        let dir = private_temp_dir("fzweb-notes")?;
+        let path = dir.join("notes.md");
+        let edited = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .and_then(|mut file| {
+                file.write_all(
+                    self.websites[index]
+                        .notes
+                        .as_deref()
+                        .unwrap_or("")
+                        .as_bytes(),
+                )
+            })
+            .map_err(FzwebError::from)
+            .and_then(|()| edit_file(&path))
+            .and_then(|()| Ok(fs::read_to_string(&path)?));
+        let _ = fs::remove_dir_all(&dir);
+        let notes = Some(edited?.trim_end().to_string()).filter(|n| !n.is_empty());
+
+        let website = &mut self.websites[index];
+        website.notes = notes.clone();
+        let name = website.name.clone();
+        self.save()?;
+        Ok(Outcome::Noted { name, notes })
+    }
+
+    fn rename_website(&mut self, old: String, new: String) -> MyResult<Outcome> {
+        let index = self.resolve(&old)?;
+        let new = new.trim().to_string();
+        if new.is_empty() {
+            return Err(FzwebError::EmptyField("name"));
+        }
+        if self
+            .websites
+            .iter()
+            .enumerate()
+            .any(|(i, w)| i != index && w.answers_to_ignoring_case(&new))
+        {
+            return Err(FzwebError::Duplicate(new));
+        }
+        let website = &mut self.websites[index];
+        let old = std::mem::replace(&mut website.name, new.clone());
+        for member in self.groups.values_mut().flatten() {
+            if *member == old {
+                *member = new.clone();
+            }
+        }
+        self.save()?;
+        Ok(Outcome::Renamed { from: old, to: new })
+    }
+
+    /// Hides the website named `name` from the picker and the list, or
+    /// brings it back, without touching anything else about it.
+    fn set_archived(&mut self, name: &str, archived: bool) -> MyResult<Outcome> {
+        let index = self.resolve(name)?;
+        let website = &mut self.websites[index];
+        website.archived = archived;
+        let name = website.name.clone();
+        self.save()?;
+        Ok(if archived {
+            Outcome::Archived { name }
+        } else {
+            Outcome::Unarchived { name }
+        })
+    }
+
+    /// Moves a website to the 1-based `position`, clamped to the ends.
+    fn move_website(&mut self, name: String, position: usize) -> MyResult<Outcome> {
+        let index = self.resolve(&name)?;
+        let website = self.websites.remove(index);
+        let position = position.clamp(1, self.websites.len() + 1);
+        let name = website.name.clone();
+        self.websites.insert(position - 1, website);
+        self.save()?;
+        Ok(Outcome::Moved { name, position })
+    }
+
+    /// Rewrites the file with the websites in `key` order, after asking,
+    /// keeping the previous file as `<file>.bak`.
+    fn sort_websites(&mut self, key: SortKey, yes: bool) -> MyResult<Outcome> {
+        let by = key
+            .to_possible_value()
+            .map_or_else(String::new, |v| v.get_name().to_string());
+        if !yes
+            && !self.report.dry_run
+            && !confirm(&format!(
+                "Rewrite the config with the websites sorted by {}?",
+                by
+            ))?
+        {
+            return Ok(Outcome::Cancelled { name: by });
+        }
+        let order: Vec<String> = sorted(&self.websites, Some(key))
+            .into_iter()
+            .map(|w| w.name.clone())
+            .collect();
+        self.websites
+            .sort_by_key(|w| order.iter().position(|name| *name == w.name));
+        self.backup()?;
+        self.save()?;
+        Ok(Outcome::Sorted {
+            by,
+            count: self.websites.len(),
+        })
+    }
+
+    /// Deletes the website named `name`, keeping the previous file as
+    /// `<file>.bak`, and returns it.
+    pub fn remove(&mut self, name: &str) -> MyResult<Website> {
+        let index = self.resolve(name)?;
+        let website = self.websites.remove(index);
+        self.backup()?;
+        self.save()?;
+        Ok(website)
+    }
+
+    fn remove_website(&mut self, name: String, yes: bool) -> MyResult<Outcome> {
+        let name = self.websites[self.resolve(&name)?].name.clone();
+        if !yes && !self.report.dry_run && !confirm(&format!("Delete '{}'?", name))? {
+            return Ok(Outcome::Cancelled { name });
+        }
+        self.remove(&name)?;
+        Ok(Outcome::Deleted { name })
+    }
+
+    /// Deletes every website after one confirmation, keeping the previous
+    /// file as `<file>.bak`.
+    fn clear(&mut self, yes: bool) -> MyResult<Outcome> {
+        let removed = self.websites.len();
+        if !yes
+            && !self.report.dry_run
+            && !confirm(&format!(
+                "This will delete all {} websites. Continue?",
+                removed
+            ))?
+        {
+            return Ok(Outcome::Cancelled {
+                name: String::from("all"),
+            });
+        }
+        self.websites.clear();
+        self.backup()?;
+        self.save()?;
+        Ok(Outcome::Cleared { removed })
+    }
+
+    /// Removes websites whose URL answers a HEAD request with 4xx/5xx or
+    /// can't be reached at all, after listing them and asking once. URLs
+    /// with unset variables or placeholders aren't checked, and when no
+    /// website answers at all nothing is removed, since that means the
+    /// network is down rather than every link being dead.
+    fn prune(&mut self, yes: bool) -> MyResult<Outcome> {
+        let client = reqwest::blocking::Client::builder()
+            .timeout(HTTP_TIMEOUT)
+            .build()?;
+        let (mut dead, mut checked, mut unanswered) = (Vec::new(), 0, 0);
+        for website in &self.websites {
+            if website.command.is_some() {
+                continue;
+            }
+            let url = expand_shortcut(&self.shortcuts, &website.url);
+            let Some(url) = shellexpand::env(&url)
+                .ok()
+                .filter(|url| placeholders(url).is_empty())
+            else {
+                self.report.log(
+                    1,
+                    format_args!("skipping '{}': its URL isn't complete yet", website.name),
+                );
+                continue;
+            };
+            checked += 1;
+            if let Some(reason) = check_url(&client, &url) {
+                eprintln!("{}\t{}\t{}", website.name, website.url, reason);
+                if reason.is_unanswered() {
+                    unanswered += 1;
+                }
+                dead.push(website.name.clone());
+            }
+        }
+        if checked > 0 && unanswered == checked {
+            return Err(FzwebError::Offline(checked));
+        }
+        if dead.is_empty() {
+            return Ok(Outcome::Pruned { names: dead });
+        }
+        if !yes
+            && !self.report.dry_run
+            && !confirm(&format!("Delete {} unreachable website(s)?", dead.len()))?
+        {
+            return Ok(Outcome::Pruned { names: Vec::new() });
+        }
+        self.websites.retain(|w| !dead.contains(&w.name));
+        self.backup()?;
+        self.save()?;
+        Ok(Outcome::Pruned { names: dead })
+    }
+
+    /// Downloads `/favicon.ico` from the host of every website into a
+    /// `favicons` directory next to the config and records where each one
+    /// went. Websites whose favicon is already on disk are skipped, and a
+    /// failed download is reported without stopping the others.
+    fn fetch_favicons(&mut self) -> MyResult<Outcome> {
+        let dir = match self.path.parent() {
+            Some(dir) if !self.path.as_os_str().is_empty() => dir.join("favicons"),
+            _ => Self::dir().join("favicons"),
+        };
+        let client = reqwest::blocking::Client::builder()
+            .timeout(HTTP_TIMEOUT)
+            .build()?;
+        let (mut fetched, mut skipped, mut failed) = (0, 0, 0);
+        for website in &mut self.websites {
+            if website
+                .favicon
+                .as_deref()
+                .is_some_and(|f| Path::new(f).is_file())
+            {
+                skipped += 1;
+                continue;
+            }
+            // URLs with variables or placeholders have no fixed host
+            let Some(origin) = Url::parse(&expand_shortcut(&self.shortcuts, &website.url))
+                .ok()
+                .filter(|url| url.has_host() && !website.url.contains(['$', '{']))
+                .map(|url| url.origin().ascii_serialization())
+            else {
+                continue;
+            };
+            if self.report.dry_run {
+                fetched += 1;
+                continue;
+            }
+            let host = origin.split_once("://").map_or(origin.as_str(), |(_, h)| h);
+            let path = dir.join(format!("{}.ico", host.replace(':', "_")));
+            let result = client
+                .get(format!("{}/favicon.ico", origin))
+                .send()
+                .and_then(|r| r.error_for_status())
+                .and_then(|r| r.bytes());
+            match result {
+                Ok(bytes) if !bytes.is_empty() => {
+                    fs::create_dir_all(&dir)?;
+                    fs::write(&path, bytes)?;
+                    website.favicon = Some(path.display().to_string());
+                    fetched += 1;
+                }
+                Ok(_) => {
+                    eprintln!("{}\t{}\tempty favicon", website.name, origin);
+                    failed += 1;
+                }
+                Err(e) => {
+                    let reason = match e.status() {
+                        Some(status) => status.to_string(),
+                        None if e.is_timeout() => String::from("timed out"),
+                        None => String::from("could not connect"),
+                    };
+                    eprintln!("{}\t{}\t{}", website.name, origin, reason);
+                    failed += 1;
+                }
+            }
+        }
+        if fetched > 0 {
+            self.save()?;
+        }
+        Ok(Outcome::Fetched {
+            fetched,
+            skipped,
+            failed,
+        })
+    }
+
+    /// Adds websites to `group`, creating it if needed. Names and aliases
+    /// must refer to saved websites and are stored by name.
+    fn add_to_group(&mut self, group: String, names: Vec<String>) -> MyResult<Outcome> {
+        let names = names
+            .iter()
+            .map(|name| {
+                self.find(name)
+                    .map(|w| w.name.clone())
+                    .ok_or_else(|| FzwebError::NotFound(name.clone()))
+            })
+            .collect::<MyResult<Vec<_>>>()?;
+        let members = self.groups.entry(group.clone()).or_default();
+        let mut added = Vec::new();
+        for name in names {
+            if !members.contains(&name) {
+                members.push(name.clone());
+                added.push(name);
+            }
+        }
+        self.save()?;
+        Ok(Outcome::Grouped {
+            group,
+            names: added,
+        })
+    }
+
+    /// Returns the saved members of `group`, warning about names that no
+    /// longer match a website.
+    fn group_members(&self, group: &str) -> MyResult<Vec<String>> {
+        let members = self
+            .groups
+            .get(group)
+            .ok_or_else(|| FzwebError::NotFound(group.to_string()))?;
+        Ok(members
+            .iter()
+            .filter(|name| {
+                let saved = self.websites.iter().any(|w| w.name == **name);
+                if !saved {
+                    eprintln!(
+                        "Warning: group '{}' lists '{}', which is not saved.",
+                        group, name
+                    );
+                }
+                saved
+            })
+            .cloned()
+            .collect())
+    }
+
+    /// Prints the websites whose URL wouldn't be accepted by `add`, and
+    /// fails if there are any. Unlike `prune`, nothing is fetched.
+    fn check_urls(&self, json: bool) -> MyResult<()> {
+        let invalid: Vec<&Website> = self
+            .websites
+            .iter()
+            .filter(|w| self.validate_url(&w.url).is_err())
+            .collect();
+        if json {
+            println!("{}", serde_json::to_string(&invalid)?);
+        } else if invalid.is_empty() {
+            if !self.report.quiet {
+                println!("All {} URL(s) are valid.", self.count());
+            }
+        } else {
+            let rows: Vec<Row> = invalid
+                .iter()
+                .map(|w| (w.label(), w.url.as_str(), None))
+                .collect();
+            print_rows(&rows);
+        }
+        if !invalid.is_empty() {
+            return Err(FzwebError::InvalidUrls(invalid.len()));
+        }
+        Ok(())
+    }
+
+    fn list_groups(&self, json: bool) -> MyResult<()> {
+        if json {
+            println!("{}", serde_json::to_string(&self.groups)?);
+            return Ok(());
+        }
+        if self.groups.is_empty() {
+            println!("No groups saved.");
+            return Ok(());
+        }
+        for group in self.groups.keys() {
+            println!("{}\t{}", group, self.group_members(group)?.join(", "));
+        }
+        Ok(())
+    }
+
+    /// Saves `url` as the expansion of `name:` in website URLs. The URL is
+    /// kept as typed, trailing slash included, since paths are appended
+    /// to it.
+    fn add_shortcut(&mut self, name: String, url: String) -> MyResult<Outcome> {
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+            || matches!(name.as_str(), "http" | "https")
+        {
+            return Err(FzwebError::InvalidShortcut(name));
+        }
+        let url = url.trim().to_string();
+        validate_url(&url).ok_or_else(|| FzwebError::InvalidUrl(url.clone()))?;
+        self.shortcuts.insert(name.clone(), url.clone());
+        self.save()?;
+        Ok(Outcome::ShortcutSet { name, url })
+    }
+
+    fn list_shortcuts(&self, json: bool) -> MyResult<()> {
+        if json {
+            println!("{}", serde_json::to_string(&self.shortcuts)?);
+            return Ok(());
+        }
+        if self.shortcuts.is_empty() {
+            println!("No shortcuts saved.");
+            return Ok(());
+        }
+        let rows: Vec<Row> = self
+            .shortcuts
+            .iter()
+            .map(|(name, url)| (format!("{}:", name), url.as_str(), None))
+            .collect();
+        print_rows(&rows);
+        Ok(())
+    }
+
+    /// Stores `value` under `key` in the config metadata; an empty value
+    /// removes the key.
+    fn set_meta(&mut self, key: String, value: String) -> MyResult<Outcome> {
+        let value = Some(value).filter(|v| !v.is_empty());
+        match &value {
+            Some(value) => self.metadata.insert(key.clone(), value.clone()),
+            None => self.metadata.remove(&key),
+        };
+        self.save()?;
+        Ok(Outcome::MetaSet { key, value })
+    }
+
+    fn get_meta(&self, key: &str) -> MyResult<&str> {
+        self.metadata
+            .get(key)
+            .map(String::as_str)
+            .ok_or_else(|| FzwebError::NoMeta(key.to_string()))
+    }
+
+    /// Sets the tag `open` filters by when no `--tag` is given; an empty
+    /// tag clears it.
+    fn set_default_tag(&mut self, tag: String) -> MyResult<Outcome> {
+        let tag = Some(tag).filter(|t| !t.is_empty());
+        self.default_tag = tag.clone();
+        self.save()?;
+        Ok(Outcome::DefaultTagSet { tag })
+    }
+
+    /// Sets what opening a website does by default; `open`, the built-in
+    /// default, is stored as no setting at all.
+    fn set_default_action(&mut self, action: Action) -> MyResult<Outcome> {
+        self.default_action = Some(action).filter(|a| *a != Action::Open);
+        self.save()?;
+        Ok(Outcome::DefaultActionSet {
+            default_action: action,
+        })
+    }
+
+    fn merge(&mut self, other: Config) -> MyResult<Outcome> {
+        self.merge_websites(numbered("website", other.websites))
+    }
+
+    /// Adds the websites that pass the checks of [`Config::add`], backing
+    /// the file up and saving once at the end. Those whose name or an alias
+    /// is taken are counted as skipped; any other rejection is reported
+    /// with where the website came from, such as `line 3`.
+    fn merge_websites(&mut self, websites: Vec<(String, Website)>) -> MyResult<Outcome> {
+        let (mut added, mut skipped) = (0, 0);
+        for (origin, website) in websites {
+            match self.check_new(website) {
+                Ok(website) => {
+                    self.websites.push(website);
+                    added += 1;
+                }
+                Err(FzwebError::Duplicate(_)) => skipped += 1,
+                Err(
+                    e @ (FzwebError::InvalidUrl(_)
+                    | FzwebError::EmptyField(_)
+                    | FzwebError::EmptyCommand(_)),
+                ) => eprintln!("{}: {}", origin, e),
+                Err(e) => return Err(e),
+            }
+        }
+        if added > 0 {
+            self.backup()?;
+            self.save()?;
+        }
+        Ok(Outcome::Imported { added, skipped })
+    }
+
+    /// Imports every http(s) link of a browser's bookmarks export, tagged
+    /// with the folders it was filed under. The file is parsed completely
+    /// before anything is added.
+    fn import_bookmarks(&mut self, path: &Path) -> MyResult<Outcome> {
+        let error = |message: String| FzwebError::Bookmarks {
+            path: path.to_path_buf(),
+            message,
+        };
+        let html = fs::read_to_string(path).map_err(|e| error(e.to_string()))?;
+        if !html.to_ascii_lowercase().contains("<dl") {
+            return Err(error(String::from(
+                "not a Netscape bookmarks file (no <DL> list found)",
+            )));
+        }
+        let (websites, ignored): (Vec<Website>, Vec<Website>) = parse_bookmarks(&html)
+            .into_iter()
+            .map(|mut w| {
+                w.url = validate_url(&w.url).unwrap_or_default();
+                w
+            })
+            .partition(|w| !w.url.is_empty());
+        if !ignored.is_empty() {
+            eprintln!(
+                "Note: ignored {} bookmark(s) that aren't http(s) URLs.",
+                ignored.len()
+            );
+        }
+        self.merge_websites(numbered("bookmark", websites))
+    }
+
+    /// All saved websites in config order.
+    pub fn list(&self) -> &[Website] {
+        &self.websites
+    }
+
+    /// Prints the websites; `archived` picks only those that are (`true`) or
+    /// aren't (`false`) archived, `None` prints all of them.
+    fn list_websites(
+        &self,
+        sort: Option<SortKey>,
+        archived: Option<bool>,
+        json: bool,
+        format: Option<&str>,
+    ) -> MyResult<()> {
+        let websites = sorted(
+            self.websites
+                .iter()
+                .filter(|w| archived.is_none_or(|a| w.archived == a)),
+            sort,
+        );
+        if json {
+            println!("{}", serde_json::to_string(&websites)?);
+            return Ok(());
+        }
+        if let Some(template) = format {
+            print_formatted(&websites, template);
+            return Ok(());
+        }
+        if websites.is_empty() {
+            match archived {
+                Some(true) => println!("No websites are archived."),
+                _ => println!("No websites saved."),
+            }
+            return Ok(());
+        }
+        let rows: Vec<Row> = websites
+            .iter()
+            .map(|w| (w.label(), w.url.as_str(), w.last_opened_ago()))
+            .collect();
+        print_rows(&rows);
+        Ok(())
+    }
+
+    /// Returns the website saved under `name` or one of its aliases,
+    /// ignoring case if that is enough to single one out.
+    pub fn find(&self, name: &str) -> Option<&Website> {
+        self.resolve(name).ok().map(|index| &self.websites[index])
+    }
+
+    /// Finds the index of the website answering to `name`. An exact match
+    /// wins; otherwise a case-insensitive one is used, unless several
+    /// websites match that way.
+    fn resolve(&self, name: &str) -> MyResult<usize> {
+        if let Some(index) = self.websites.iter().position(|w| w.answers_to(name)) {
+            return Ok(index);
+        }
+        let candidates: Vec<usize> = (0..self.websites.len())
+            .filter(|&i| self.websites[i].answers_to_ignoring_case(name))
+            .collect();
+        match candidates[..] {
+            [] => Err(FzwebError::NotFound(name.to_string())),
+            [index] => Ok(index),
+            _ => Err(FzwebError::Ambiguous {
+                name: name.to_string(),
+                candidates: candidates
+                    .iter()
+                    .map(|&i| self.websites[i].name.clone())
+                    .collect(),
+            }),
+        }
+    }
+
+    fn count(&self) -> usize {
+        self.websites.len()
+    }
+
+    fn stats(&self) -> Stats<'_> {
+        let mut tags = BTreeMap::new();
+        for tag in self.websites.iter().flat_map(|w| &w.tags) {
+            *tags.entry(tag.as_str()).or_insert(0) += 1;
+        }
+
+        let mut opened: Vec<&Website> = self.websites.iter().filter(|w| w.open_count > 0).collect();
+        opened.sort_by_key(|website| Reverse(website.open_count));
+        let most_opened = opened
+            .into_iter()
+            .take(5)
+            .map(|w| (w.name.as_str(), w.open_count))
+            .collect();
+
+        Stats {
+            total: self.count(),
+            tags,
+            most_opened,
+        }
+    }
+
+    fn search(&self, query: &str) -> Vec<&Website> {
+        self.websites.iter().filter(|w| w.contains(query)).collect()
+    }
+
+    fn open_website(&mut self, options: &OpenOptions, selector: &dyn Selector) -> MyResult<()> {
+        if let Some(name) = &options.name {
+            return self.launch(name, options);
+        }
+
+        if let Some(group) = &options.group {
+            let names = self.group_members(group)?;
+            return self.launch_all(names, options);
+        }
+
+        // pick a tag first, then a website carrying it
+        let by_tag_options;
+        let options = if options.by_tag {
+            let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+            for tag in self
+                .websites
+                .iter()
+                .filter(|w| !w.archived)
+                .flat_map(|w| &w.tags)
+            {
+                *counts.entry(tag).or_default() += 1;
+            }
+            if counts.is_empty() {
+                return Err(FzwebError::NoTags);
+            }
+            let items: Vec<PickerItem> = counts
+                .iter()
+                .map(|(tag, count)| PickerItem::tag(tag, *count))
+                .collect();
+            if options.emit {
+                emit(&items);
+                return Ok(());
+            }
+            let tag = select(selector, items, self.skim(), None)?;
+            by_tag_options = OpenOptions {
+                tags: vec![tag],
+                ..options.clone()
+            };
+            &by_tag_options
+        } else {
+            options
+        };
+
+        let tags = &options.tags;
+        let mut websites = sorted(
+            self.websites
+                .iter()
+                .filter(|website| !website.archived && website.has_tags(tags)),
+            options.sort,
+        );
+
+        if websites.is_empty() && !tags.is_empty() {
+            return Err(FzwebError::NoTagged(tags.clone()));
+        }
+
+        if options.all {
+            let names: Vec<String> = websites.iter().map(|w| w.name.clone()).collect();
+            return self.launch_all(names, options);
+        }
+
+        if options.random {
+            let website = websites
+                .choose(&mut rand::rng())
+                .ok_or(FzwebError::NoWebsites)?;
+            let name = website.name.clone();
+            return self.launch_selected(&name, options);
+        }
+
+        // sorted by SortKey::Neglected, so the first one is the least used
+        if options.least_used {
+            let website = websites.first().ok_or(FzwebError::NoWebsites)?;
+            let name = website.name.clone();
+            return self.launch_selected(&name, options);
+        }
+
+        // a query only one website matches needs no picker
+        if options.exact
+            && let Some(query) = &options.query
+        {
+            let matching: Vec<&&Website> = websites.iter().filter(|w| w.contains(query)).collect();
+            match matching[..] {
+                [] if options.create => return self.create_from_query(query.clone(), options),
+                [] => return Err(FzwebError::NoMatch(query.clone())),
+                [website] => {
+                    let name = website.name.clone();
+                    return self.launch_selected(&name, options);
+                }
+                _ => {}
+            }
+        }
+
+        // most opened first unless asked otherwise, insertion order among equals
+        if options.sort.is_none() {
+            websites.sort_by_key(|website| Reverse(website.open_count));
+        }
+        if let Some(limit) = options.limit
+            && websites.len() > limit
+        {
+            eprintln!(
+                "Note: showing the first {} of {} websites (--limit).",
+                limit,
+                websites.len()
+            );
+            websites.truncate(limit);
+        }
+        let items: Vec<PickerItem> = websites.into_iter().map(PickerItem::new).collect();
+        self.report.log(
+            2,
+            format_args!("offering {} website(s) to pick from", items.len()),
+        );
+        if options.emit {
+            emit(&items);
+            return Ok(());
+        }
+
+        let picked = match select_to_open(selector, items, self.skim(), options) {
+            Err(FzwebError::NoMatch(name)) if options.create => {
+                return self.create_from_query(name, options);
+            }
+            picked => picked?,
+        };
+        let copy_options;
+        let options = if picked.copy {
+            copy_options = OpenOptions {
+                action: Action::Copy,
+                ..options.clone()
+            };
+            &copy_options
+        } else {
+            options
+        };
+        for name in picked.names {
+            self.launch_selected(&name, options)?;
+        }
+        Ok(())
+    }
+
+    /// Offers to save `name`, typed into the picker without matching
+    /// anything, under a URL read from the terminal, then opens it.
+    fn create_from_query(&mut self, name: String, options: &OpenOptions) -> MyResult<()> {
+        let url = ask(&format!(
+            "'{}' is not saved. URL to add it under (empty to cancel):",
+            name
+        ))?;
+        let report = self.open_report(options);
+        if url.is_empty() {
+            return Outcome::Cancelled { name }.report(report);
+        }
+        let website = Website {
+            name: name.clone(),
+            url,
+            tags: options.tags.clone(),
+            ..Website::default()
+        };
+        // the picker ran without the lock, so the file may have changed
+        self.reload_locked()?;
+        self.add_website(website, false)?.report(report)?;
+        self.launch(&name, options)
+    }
+
+    /// Launches a website that was picked rather than named on the command
+    /// line, saying which one it is.
+    fn launch_selected(&mut self, name: &str, options: &OpenOptions) -> MyResult<()> {
+        if !self.open_report(options).quiet {
+            println!("{} is selected", name);
+        }
+        self.launch(name, options)
+    }
+
+    /// How to report while opening: stdout carries nothing but URLs when
+    /// printing them, so everything else is left out then.
+    fn open_report(&self, options: &OpenOptions) -> Report {
+        match options.action {
+            Action::Print => Report {
+                json: false,
+                quiet: true,
+                ..self.report
+            },
+            Action::Open | Action::Copy => self.report,
+        }
+    }
+
+    fn launch_all(&mut self, names: Vec<String>, options: &OpenOptions) -> MyResult<()> {
+        let total = names.len();
+        if total > OPEN_ALL_CONFIRM_THRESHOLD
+            && !options.yes
+            && !confirm(&format!("Open {} websites?", total))?
+        {
+            return Outcome::Cancelled {
+                name: String::from("all"),
+            }
+            .report(self.open_report(options));
+        }
+
+        let mut failures = vec![];
+        for name in names {
+            if let Err(e) = self.launch(&name, options) {
+                failures.push((name, e));
+            }
+        }
+
+        if failures.is_empty() {
+            return Ok(());
+        }
+        for (name, e) in &failures {
+            eprintln!("{}: {}", name, e);
+        }
+        Err(FzwebError::PartialFailure {
+            failed: failures.len(),
+            total,
+        })
+    }
+
+    fn launch(&mut self, name: &str, options: &OpenOptions) -> MyResult<()> {
+        let index = self.resolve(name)?;
+        let report = self.report;
+        let website = &mut self.websites[index];
+        report.log(1, format_args!("selected '{}'", website.name));
+        let mut url = shellexpand::env(&expand_shortcut(&self.shortcuts, &website.url))
+            .map_err(|e| FzwebError::UnsetVariable(e.var_name))?
+            .into_owned();
+        url = fill_placeholders(&url)?;
+        if !options.params.is_empty() {
+            let mut parsed = Url::parse(&url).map_err(|_| FzwebError::InvalidUrl(url.clone()))?;
+            parsed.query_pairs_mut().extend_pairs(&options.params);
+            url = parsed.into();
+        }
+
+        match options.action {
+            Action::Copy => {
+                copy_to_clipboard(&url)?;
+                if !self.report.quiet {
+                    println!("Copied {} URL to clipboard.", website.name);
+                }
+                return Ok(());
+            }
+            Action::Print => {
+                println!("{}", url);
+                return Ok(());
+            }
+            Action::Open => {}
+        }
+
+        if options.peek && !options.yes {
+            println!("{}  {}", website.name, url);
+            if !confirm_default_yes(&format!("Open {}?", url))? {
+                return Outcome::Cancelled {
+                    name: website.name.clone(),
+                }
+                .report(report);
+            }
+        }
+
+        let times = options.repeat.max(1);
+        if times > REPEAT_CONFIRM_THRESHOLD
+            && !options.yes
+            && !confirm(&format!("Open {} {} times?", url, times))?
+        {
+            return Outcome::Cancelled {
+                name: website.name.clone(),
+            }
+            .report(report);
+        }
+        for time in 0..times {
+            // keep the browser from folding quick repeats into one tab
+            if time > 0 {
+                thread::sleep(REPEAT_DELAY);
+            }
+            if let Some(command) = &website.command {
+                if command.trim().is_empty() {
+                    return Err(FzwebError::EmptyCommand(website.name.clone()));
+                }
+                report.log(1, format_args!("running `{}` for {}", command, url));
+                run_command(command, &url)?;
+            } else {
+                if options.browser.is_none() && is_headless() {
+                    return Err(FzwebError::NoBrowser(url));
+                }
+                report.log(1, format_args!("opening {}", url));
+                report.log(
+                    2,
+                    format_args!(
+                        "browser: {}",
+                        configured_browser(options)
+                            .unwrap_or_else(|| String::from("system default"))
+                    ),
+                );
+                // a browser that is still starting up may reject the first
+                // request, so give it one more chance
+                open_url(&url, options)
+                    .or_else(|e| {
+                        eprintln!(
+                            "Note: opening {} failed ({}); retrying in {}s.",
+                            url,
+                            e,
+                            options.retry_delay.as_secs()
+                        );
+                        thread::sleep(options.retry_delay);
+                        open_url(&url, options)
+                    })
+                    .map_err(|source| FzwebError::Open {
+                        url: url.clone(),
+                        source,
+                    })?;
+            }
+        }
+        if let Some(notes) = &website.notes
+            && !self.report.quiet
+        {
+            eprintln!("{}", notes);
+        }
+        if let Some(hook) = &self.on_open {
+            report.log(2, format_args!("running on_open hook `{}`", hook));
+            run_hook(hook, &website.name, &url);
+        }
+        self.record_open(index)
+    }
+
+    /// Counts an opening of the website at `index` and saves it. Unless the
+    /// lock has been held since loading, the file is read again under the
+    /// lock first, so that what other fzweb processes saved while this one
+    /// sat in the picker is kept. The website has been opened by then, so
+    /// a lock held elsewhere only costs the count and is merely reported.
+    /// Replaces the websites and settings with those on disk, taking the
+    /// lock for the rest of the run. The settings of this run are kept.
+    /// Nothing happens when the lock is already held or nothing is written.
+    fn reload_locked(&mut self) -> MyResult<()> {
+        if self.lock.is_some()
+            || self.report.dry_run
+            || self.path.as_os_str().is_empty()
+            || !self.path.exists()
+        {
+            return Ok(());
+        }
+        let mut fresh = Config::load_locked(&self.path)?;
+        fresh.report = self.report;
+        fresh.cli_skim = self.cli_skim.take();
+        *self = fresh;
+        Ok(())
+    }
+
+    fn record_open(&mut self, index: usize) -> MyResult<()> {
+        let opened_at = Some(Utc::now().to_rfc3339());
+        let website = &mut self.websites[index];
+        website.open_count += 1;
+        website.last_opened = opened_at.clone();
+        if self.lock.is_some()
+            || self.report.dry_run
+            || self.path.as_os_str().is_empty()
+            || !self.path.exists()
+        {
+            return self.save();
+        }
+        let name = website.name.clone();
+        let mut fresh = match Config::load_locked(&self.path) {
+            Ok(fresh) => fresh,
+            Err(e @ FzwebError::Locked(_)) => {
+                eprintln!("Warning: the visit to '{}' wasn't counted: {}", name, e);
+                return Ok(());
+            }
+            Err(e) => return Err(e),
+        };
+        // deleted or renamed meanwhile: nothing left to count it for
+        let Some(website) = fresh.websites.iter_mut().find(|w| w.name == name) else {
+            return Ok(());
+        };
+        website.open_count += 1;
+        website.last_opened = opened_at;
+        fresh.save()
+    }
+}
+
+/// Puts `url` on the clipboard. X11 and Wayland only serve the clipboard
+/// while the process that set it runs, so there a background fzweb takes
+/// the text over and keeps it until something else is copied.
+fn copy_to_clipboard(url: &str) -> MyResult<()> {
+    // set it here as well, so that a missing display is still reported
+    Clipboard::new()?.set_text(url)?;
+    #[cfg(all(
+        unix,
+        not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))
+    ))]
+    {
+        let mut server = process::Command::new(env::current_exe()?)
+            .arg(SERVE_CLIPBOARD)
+            .stdin(process::Stdio::piped())
+            .stdout(process::Stdio::null())
+            .stderr(process::Stdio::null())
+            .current_dir("/")
+            .spawn()?;
+        server
+            .stdin
+            .take()
+            .expect("stdin is piped")
+            .write_all(url.as_bytes())?;
+    }
+    Ok(())
+}
+
+/// Sets the clipboard to the text on stdin and, on X11 and Wayland, keeps
+/// serving it until another application replaces it.
+fn serve_clipboard() -> MyResult<()> {
+    let text = io::read_to_string(io::stdin())?;
+    #[cfg(all(
+        unix,
+        not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))
+    ))]
+    Clipboard::new()?.set().wait().text(text)?;
+    #[cfg(not(all(
+        unix,
+        not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))
+    )))]
+    Clipboard::new()?.set_text(text)?;
+    Ok(())
+}
+
+/// Why a URL looks dead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DeadUrl {
+    Status(reqwest::StatusCode),
+    TimedOut,
+    NoConnection,
+}
+
+impl DeadUrl {
+    /// Whether the server never answered, which a missing network causes
+    /// just as well as a dead website.
+    fn is_unanswered(self) -> bool {
+        !matches!(self, DeadUrl::Status(_))
+    }
+}
+
+impl fmt::Display for DeadUrl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeadUrl::Status(status) => write!(f, "{}", status),
+            DeadUrl::TimedOut => write!(f, "timed out"),
+            DeadUrl::NoConnection => write!(f, "could not connect"),
+        }
+    }
+}
+
+/// Returns why `url` looks dead, or `None` if it answered with a success or
+/// redirect. Servers that refuse HEAD get a second chance with GET.
+fn check_url(client: &reqwest::blocking::Client, url: &str) -> Option<DeadUrl> {
+    let mut response = client.head(url).send();
+    if let Ok(r) = &response
+        && r.status() == reqwest::StatusCode::METHOD_NOT_ALLOWED
+    {
+        response = client.get(url).send();
+    }
+    match response {
+        Ok(r) if r.status().is_client_error() || r.status().is_server_error() => {
+            Some(DeadUrl::Status(r.status()))
+        }
+        Ok(_) => None,
+        Err(e) if e.is_timeout() => Some(DeadUrl::TimedOut),
+        Err(_) => Some(DeadUrl::NoConnection),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BrowserKind {
+    Chromium,
+    Firefox,
+}
+
+impl BrowserKind {
+    /// Recognizes Chrome, Chromium, Brave and Firefox from a command name or
+    /// path such as `google-chrome-stable` or `/usr/bin/firefox`.
+    fn detect(browser: &str) -> Option<Self> {
+        let name = Path::new(browser).file_name().map_or_else(
+            || browser.to_lowercase(),
+            |n| n.to_string_lossy().to_lowercase(),
+        );
+        if ["chrome", "chromium", "brave"]
+            .iter()
+            .any(|b| name.contains(b))
+        {
+            Some(BrowserKind::Chromium)
+        } else if name.contains("firefox") {
+            Some(BrowserKind::Firefox)
+        } else {
+            None
+        }
+    }
+
+    fn private_flag(self) -> &'static str {
+        match self {
+            BrowserKind::Chromium => "--incognito",
+            BrowserKind::Firefox => "--private-window",
+        }
+    }
+
+    fn new_window_flag(self) -> &'static str {
+        "--new-window"
+    }
+
+    /// The arguments selecting a named browser profile: Chromium-based
+    /// browsers take the profile's directory name (`Default`, `Profile 1`),
+    /// Firefox the name shown in `about:profiles`.
+    fn profile_args(self, profile: &str) -> Vec<String> {
+        match self {
+            BrowserKind::Chromium => vec![format!("--profile-directory={}", profile)],
+            BrowserKind::Firefox => vec![String::from("-P"), profile.to_string()],
+        }
+    }
+}
+
+/// Runs a website's custom command through the shell, with every `{url}`
+/// replaced by the quoted URL.
+fn run_command(command: &str, url: &str) -> MyResult<()> {
+    #[cfg(windows)]
+    let quoted = format!("\"{}\"", url);
+    #[cfg(not(windows))]
+    let quoted = format!("'{}'", url.replace('\'', "'\\''"));
+
+    let command = command.replace("{url}", &quoted);
+    shell(&command)
+        .spawn()
+        .map_err(|source| FzwebError::Command { command, source })?;
+    Ok(())
+}
+
+/// Creates a new directory under the system temp directory that only the
+/// current user can enter. It gets a random name and must not exist yet, so
+/// nothing placed there in advance, such as a symlink, can be used.
+fn private_temp_dir(prefix: &str) -> io::Result<PathBuf> {
+    let mut builder = fs::DirBuilder::new();
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    let dir = env::temp_dir().join(format!(
+        "{}-{}-{:016x}",
+        prefix,
+        process::id(),
+        rand::random::<u64>()
+    ));
+    builder.create(&dir)?;
+    Ok(dir)
+}
+
+/// Runs `$VISUAL` or `$EDITOR` (vi, or Notepad on Windows, if neither is
+/// set) on `path` and waits for it to exit.
+fn edit_file(path: &Path) -> MyResult<()> {
+    let editor = ["VISUAL", "EDITOR"]
+        .iter()
+        .filter_map(env::var_os)
+        .map(|v| v.to_string_lossy().into_owned())
+        .find(|v| !v.trim().is_empty())
+        .unwrap_or_else(|| String::from(if cfg!(windows) { "notepad" } else { "vi" }));
+    let command = format!("{} \"{}\"", editor, path.display());
+    let status = shell(&command)
+        .status()
+        .map_err(|e| io::Error::new(e.kind(), format!("could not run `{}`: {}", command, e)))?;
+    if !status.success() {
+        return Err(io::Error::other(format!("`{}` exited with {}", command, status)).into());
+    }
+    Ok(())
+}
+
+/// A `Command` running `command` through the platform shell.
+fn shell(command: &str) -> process::Command {
+    #[cfg(windows)]
+    let (shell, flag) = ("cmd", "/C");
+    #[cfg(not(windows))]
+    let (shell, flag) = ("sh", "-c");
+
+    let mut process = process::Command::new(shell);
+    process.arg(flag).arg(command);
+    process
+}
+
+/// Runs the `on_open` hook with `FZWEB_NAME` and `FZWEB_URL` set. A failing
+/// hook is only reported; the website has been opened either way.
+fn run_hook(hook: &str, name: &str, url: &str) {
+    let status = shell(hook)
+        .env("FZWEB_NAME", name)
+        .env("FZWEB_URL", url)
+        .status();
+    match status {
+        Ok(status) if status.success() => {}
+        Ok(status) => eprintln!("Warning: on_open hook `{}` exited with {}.", hook, status),
+        Err(e) => eprintln!("Warning: failed to run on_open hook `{}`: {}", hook, e),
+    }
+}
+
+/// The browser named with `--browser`, or else the first entry of `$BROWSER`.
+fn configured_browser(options: &OpenOptions) -> Option<String> {
+    options.browser.clone().or_else(|| {
+        env::var("BROWSER")
+            .ok()
+            .and_then(|b| b.split(':').next().map(ToString::to_string))
+            .filter(|b| !b.is_empty())
+    })
+}
+
+/// True on Linux and the BSDs when there is no graphical session and no
+/// `$BROWSER` to fall back on, which is the situation on an SSH session
+/// into a server. Desktops always set one of the display variables.
+/// WSL never counts as headless since it can hand URLs to Windows.
+fn is_headless() -> bool {
+    cfg!(all(unix, not(target_os = "macos")))
+        && !is_wsl()
+        && ["DISPLAY", "WAYLAND_DISPLAY", "BROWSER"]
+            .iter()
+            .all(|var| env::var_os(var).is_none_or(|v| v.is_empty()))
+}
+
+fn open_url(url: &str, options: &OpenOptions) -> io::Result<()> {
+    if options.private || options.new_window || options.browser_profile.is_some() {
+        let browser = configured_browser(options);
+        match browser.as_deref().and_then(BrowserKind::detect) {
+            Some(kind) => {
+                let mut args = Vec::new();
+                if let Some(profile) = &options.browser_profile {
+                    args.extend(kind.profile_args(profile));
+                }
+                // private windows are always new windows already
+                if options.private {
+                    args.push(kind.private_flag().to_string());
+                } else if options.new_window {
+                    args.push(kind.new_window_flag().to_string());
+                }
+                process::Command::new(browser.unwrap_or_default())
+                    .args(args)
+                    .arg(url)
+                    .spawn()?;
+                return Ok(());
+            }
+            None => {
+                let what = if options.private {
+                    "private windows"
+                } else if options.new_window {
+                    "new windows"
+                } else {
+                    "browser profiles"
+                };
+                eprintln!(
+                    "Warning: {} need --browser (or $BROWSER) set to Chrome, Chromium, Brave or Firefox; opening normally.",
+                    what
+                );
+            }
+        }
+    }
+
+    match &options.browser {
+        Some(browser) => open::with(url, browser),
+        None if is_wsl() => open::that(url).or_else(|_| open_in_windows(url)),
+        None => open::that(url),
+    }
+}
+
+/// True when running under the Windows Subsystem for Linux.
+fn is_wsl() -> bool {
+    cfg!(target_os = "linux")
+        && fs::read_to_string("/proc/version").is_ok_and(|v| v.to_lowercase().contains("microsoft"))
+}
+
+/// Opens `url` in the default Windows browser through `cmd.exe`, for WSL
+/// installs without `wslview`. cmd.exe treats `&`, `|` and friends as
+/// operators, so they are escaped with `^`.
+fn open_in_windows(url: &str) -> io::Result<()> {
+    let escaped: String = url
+        .chars()
+        .flat_map(|c| {
+            let caret = "&|<>^()%!".contains(c).then_some('^');
+            caret.into_iter().chain([c])
+        })
+        .collect();
+    let status = process::Command::new("cmd.exe")
+        .args(["/c", "start", "\"\""])
+        .arg(escaped)
+        .stdout(process::Stdio::null())
+        .stderr(process::Stdio::null())
+        .status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!("cmd.exe exited with {}", status)))
+    }
+}
+
+/// Prints a PASS/WARN/FAIL checklist for the config at `path` and the
+/// environment, failing if any FAIL line was printed.
+fn doctor(path: &Path) -> MyResult<()> {
+    let mut failed = 0;
+    let mut check = |level: &str, message: String| {
+        if level == "FAIL" {
+            failed += 1;
+        }
+        println!("{}  {}", level, message);
+    };
+
+    if !path.exists() {
+        check(
+            "WARN",
+            format!(
+                "config file {} doesn't exist yet; it is created on the first add",
+                path.display()
+            ),
+        );
+    } else {
+        check("PASS", format!("config file {} exists", path.display()));
+        match Config::load(path) {
+            Ok(config) => {
+                check("PASS", String::from("config file parses"));
+                check("PASS", format!("{} website(s) saved", config.count()));
+                let invalid: Vec<&str> = config
+                    .websites
+                    .iter()
+                    .filter(|w| config.validate_url(&w.url).is_err())
+                    .map(|w| w.name.as_str())
+                    .collect();
+                if invalid.is_empty() {
+                    check("PASS", String::from("every URL is valid"));
+                } else {
+                    check("FAIL", format!("invalid URLs: {}", invalid.join(", ")));
+                }
+            }
+            Err(e) => check("FAIL", e.to_string()),
+        }
+    }
+    match configured_browser(&OpenOptions::default()) {
+        Some(browser) => check("PASS", format!("browser: {} (from $BROWSER)", browser)),
+        None if is_headless() => check(
+            "WARN",
+            String::from("no display and $BROWSER is not set; use --url-only or --copy"),
+        ),
+        None => check("PASS", String::from("browser: system default")),
+    }
+
+    if failed > 0 {
+        return Err(FzwebError::ChecksFailed(failed));
+    }
+    Ok(())
+}
+
+/// The advisory lock guarding changes to a config, an exclusively locked
+/// `<config>.lock` file next to it. The file is removed again when the lock
+/// is dropped.
+#[derive(Debug)]
+struct ConfigLock {
+    // held only for the lock on it
+    _file: fs::File,
+    path: PathBuf,
+}
+
+impl ConfigLock {
+    /// Takes the lock of the config at `path`. Another fzweb changing the
+    /// config at the same time would clobber this change or have it
+    /// clobbered, so rather than wait this fails right away if it is taken.
+    fn take(path: &Path) -> MyResult<Self> {
+        let lock_path = with_suffix(path, ".lock");
+        // a holder can remove the file between it being opened here and
+        // locked, leaving a lock on a file nobody else will ever open
+        for _ in 0..3 {
+            let file = fs::OpenOptions::new()
+                .create(true)
+                .truncate(false)
+                .write(true)
+                .open(&lock_path)
+                .map_err(write_error(&lock_path))?;
+            match file.try_lock() {
+                Ok(()) if is_same_file(&file, &lock_path) => {
+                    return Ok(ConfigLock {
+                        _file: file,
+                        path: lock_path,
+                    });
+                }
+                Ok(()) => continue,
+                Err(fs::TryLockError::WouldBlock) => break,
+                Err(fs::TryLockError::Error(e)) => return Err(e.into()),
+            }
+        }
+        Err(FzwebError::Locked(lock_path))
+    }
+}
+
+impl Drop for ConfigLock {
+    fn drop(&mut self) {
+        // removed while still locked; the lock goes when the file is closed
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Whether `file` is still the file found at `path`. On Windows a removed
+/// file lingers until every handle to it is closed and can't be created
+/// anew before, so there it always is.
+fn is_same_file(file: &fs::File, path: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        match (file.metadata(), fs::metadata(path)) {
+            (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+            _ => false,
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = (file, path);
+        true
+    }
+}
+
+/// Creates the directory `path` goes in unless it already exists.
+fn create_parent_dir(path: &Path) -> MyResult<()> {
+    if let Some(dir) = path.parent()
+        && !dir.as_os_str().is_empty()
+        && !dir.exists()
+    {
+        fs::create_dir_all(dir).map_err(write_error(dir))?;
+    }
+    Ok(())
+}
+
+/// Wraps an I/O error from writing `path` so the message names the file.
+fn write_error(path: &Path) -> impl FnOnce(io::Error) -> FzwebError {
+    let path = path.to_path_buf();
+    move |source| FzwebError::ConfigWrite { path, source }
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+/// A name, its URL and an optional note such as when it was last opened.
+type Row<'a> = (String, &'a str, Option<String>);
+
+/// Prints `name<TAB>url` lines, or, when stdout is a terminal, bold names
+/// with the URLs aligned in a column and any note after them. Color is
+/// dropped if `NO_COLOR` is set.
+fn print_rows(rows: &[Row]) {
+    if !io::stdout().is_terminal() {
+        for (name, url, _) in rows {
+            println!("{}\t{}", name, url);
+        }
+        return;
+    }
+    let (bold, dim, reset) = if env::var_os("NO_COLOR").is_some() {
+        ("", "", "")
+    } else {
+        ("\x1b[1;36m", "\x1b[2m", "\x1b[0m")
+    };
+    let width = rows
+        .iter()
+        .map(|(name, _, _)| name.chars().count())
+        .max()
+        .unwrap_or(0);
+    for (name, url, note) in rows {
+        print!("{}{:<width$}{}  {}", bold, name, reset, url, width = width);
+        match note {
+            Some(note) => println!("  {}{}{}", dim, note, reset),
+            None => println!(),
+        }
+    }
+}
+
+/// Fields a `--format` template can refer to.
+const FORMAT_FIELDS: [&str; 4] = ["name", "url", "tags", "description"];
+
+fn parse_format(template: &str) -> Result<String, String> {
+    match placeholders(template)
+        .into_iter()
+        .find(|field| !FORMAT_FIELDS.contains(&field.as_str()))
+    {
+        Some(field) => Err(format!(
+            "unknown field {{{}}}; expected one of {{name}}, {{url}}, {{tags}} or {{description}}",
+            field
+        )),
+        None => Ok(template.to_string()),
+    }
+}
+
+/// Prints one line per website with the `--format` fields substituted.
+/// Tags are joined with commas; a missing description is left empty.
+fn print_formatted(websites: &[&Website], template: &str) {
+    for website in websites {
+        let line = template
+            .replace("{name}", &website.name)
+            .replace("{url}", &website.url)
+            .replace("{tags}", &website.tags.join(","))
+            .replace(
+                "{description}",
+                website.description.as_deref().unwrap_or(""),
+            );
+        println!("{}", line);
+    }
+}
+
+fn confirm(prompt: &str) -> MyResult<bool> {
+    let answer = ask(&format!("{} [y/N]", prompt))?;
+    Ok(matches!(answer.to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Like [`confirm`], but an empty answer means yes.
+fn confirm_default_yes(prompt: &str) -> MyResult<bool> {
+    let answer = ask(&format!("{} [Y/n]", prompt))?;
+    Ok(matches!(answer.to_lowercase().as_str(), "" | "y" | "yes"))
+}
+
+/// Prints `prompt` to stderr and returns the trimmed line typed in reply.
+fn ask(prompt: &str) -> MyResult<String> {
+    if !io::stdin().is_terminal() {
+        return Err(FzwebError::NotInteractive);
+    }
+    eprint!("{} ", prompt);
+    io::stderr().flush()?;
+
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(answer.trim().to_string())
+}
+
+/// Puts a URL in the form it is stored in: `https://` is added when there
+/// is no scheme, the scheme and host are lowercased and a lone trailing
+/// `/` is dropped. Paths and query strings are kept exactly as typed.
+fn normalize_url(url: &str) -> String {
+    let url = url.trim();
+    let (scheme, rest) = url.split_once("://").unwrap_or(("https", url));
+    let (authority, path) = rest.split_at(rest.find(['/', '?', '#']).unwrap_or(rest.len()));
+    // leave hosts that come from a variable alone, `$HOST` is case-sensitive
+    let authority = match authority.rsplit_once('@') {
+        _ if authority.contains('$') => authority.to_string(),
+        Some((user, host)) => format!("{}@{}", user, host.to_lowercase()),
+        None => authority.to_lowercase(),
+    };
+    let path = if path == "/" { "" } else { path };
+    format!("{}://{}{}", scheme.to_lowercase(), authority, path)
+}
+
+fn validate_url(url: &str) -> Option<String> {
+    let url = normalize_url(url);
+
+    // variables are only expanded at open time, so check the shape of the URL
+    // with a stand-in value for each of them
+    let expanded = shellexpand::env_with_context_no_errors(&url, |_| Some("x"));
+
+    match Url::parse(&expanded) {
+        Ok(parsed) if matches!(parsed.scheme(), "http" | "https") && parsed.has_host() => Some(url),
+        _ => None,
+    }
+}
+
+/// Replaces a leading `name:` with the URL saved for the shortcut `name`.
+/// Anything else, ordinary `https://` URLs included, is left alone.
+fn expand_shortcut<'a>(shortcuts: &BTreeMap<String, String>, url: &'a str) -> Cow<'a, str> {
+    match url.split_once(':') {
+        Some((name, rest)) if !rest.starts_with("//") => match shortcuts.get(name) {
+            Some(prefix) => Cow::Owned(format!("{}{}", prefix, rest)),
+            None => Cow::Borrowed(url),
+        },
+        _ => Cow::Borrowed(url),
+    }
+}
+
+/// What two URLs for the same page have in common, for spotting
+/// duplicates: the URL without its scheme, a leading `www.` or trailing
+/// slashes on the path, and with the host lowercased. Never stored.
+fn canonical_key(url: &str) -> String {
+    let url = normalize_url(url);
+    let rest = url.split_once("://").map_or(url.as_str(), |(_, rest)| rest);
+    let rest = rest.strip_prefix("www.").unwrap_or(rest);
+    // slashes in the query or fragment, as in `?next=/`, mean something
+    let (path, suffix) = rest.split_at(rest.find(['?', '#']).unwrap_or(rest.len()));
+    format!("{}{}", path.trim_end_matches('/'), suffix)
+}
+
+/// The links in a Netscape bookmarks file, the HTML format browsers export
+/// bookmarks in. Each link is tagged with the folders (`<H3>` headings
+/// followed by a `<DL>` list) it is nested in, lowercased and with spaces
+/// turned into dashes. Links without a title are named after their URL.
+fn parse_bookmarks(html: &str) -> Vec<Website> {
+    // lowercasing ASCII keeps byte offsets, so both strings share indices
+    let lower = html.to_ascii_lowercase();
+    let text_until = |from: usize, close: &str| {
+        let to = lower[from..]
+            .find(close)
+            .map_or(html.len(), |end| from + end);
+        decode_entities(html[from..to].trim())
+    };
+
+    let mut websites = Vec::new();
+    let mut folders: Vec<Option<String>> = Vec::new();
+    let mut heading = None;
+    let mut pos = 0;
+    while let Some(offset) = lower[pos..].find('<') {
+        let start = pos + offset + 1;
+        let end = lower[start..].find('>').map_or(html.len(), |e| start + e);
+        pos = (end + 1).min(html.len());
+        let tag = &lower[start..end];
+        if tag.starts_with("h3") {
+            heading = Some(text_until(pos, "</h3"));
+        } else if tag.starts_with("dl") {
+            folders.push(heading.take());
+        } else if tag.starts_with("/dl") {
+            folders.pop();
+        } else if tag.starts_with("a ")
+            && let Some(url) = attribute(&html[start..end], "href")
+        {
+            let title = text_until(pos, "</a");
+            websites.push(Website {
+                name: if title.is_empty() { url.clone() } else { title },
+                url,
+                tags: folders
+                    .iter()
+                    .flatten()
+                    .map(|folder| {
+                        folder
+                            .to_lowercase()
+                            .split_whitespace()
+                            .collect::<Vec<_>>()
+                            .join("-")
+                    })
+                    .collect(),
+                ..Website::default()
+            });
+        }
+    }
+    websites
+}
+
+/// Pairs each website with `what` and its 1-based position, e.g.
+/// `website 3`, for reporting the ones that can't be added.
+fn numbered(what: &str, websites: Vec<Website>) -> Vec<(String, Website)> {
+    websites
+        .into_iter()
+        .enumerate()
+        .map(|(index, website)| (format!("{} {}", what, index + 1), website))
+        .collect()
+}
+
+/// The decoded value of the attribute `name` inside an HTML tag, which
+/// bookmark exports always write as ` NAME="value"`.
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    let needle = format!(" {}=", name);
+    let start = tag.to_ascii_lowercase().find(&needle)? + needle.len();
+    let value = &tag[start..];
+    let value = match value.chars().next()? {
+        quote @ ('"' | '\'') => {
+            let value = &value[1..];
+            &value[..value.find(quote)?]
+        }
+        _ => value.split_whitespace().next()?,
+    };
+    Some(decode_entities(value))
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Unique `{name}` placeholders in `url`, in order of appearance.
+fn placeholders(url: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    let mut rest = url;
+    while let Some(start) = rest.find('{') {
+        rest = &rest[start + 1..];
+        let Some(end) = rest.find('}') else { break };
+        let name = &rest[..end];
+        if !name.is_empty()
+            && name.chars().all(|c| c.is_alphanumeric() || c == '_')
+            && !names.iter().any(|n| n == name)
+        {
+            names.push(name.to_string());
+        }
+    }
+    names
+}
+
+/// Asks on the terminal for each `{name}` placeholder in `url` and returns
+/// the URL with the percent-encoded answers substituted.
+fn fill_placeholders(url: &str) -> MyResult<String> {
+    let names = placeholders(url);
+    if names.is_empty() {
+        return Ok(url.to_string());
+    }
+    if !io::stdin().is_terminal() {
+        return Err(FzwebError::UnfilledPlaceholders(names));
+    }
+    let mut url = url.to_string();
+    for name in names {
+        let value = ask(&format!("{}:", name))?;
+        let value = utf8_percent_encode(&value, NON_ALPHANUMERIC).to_string();
+        url = url.replace(&format!("{{{}}}", name), &value);
+    }
+    Ok(url)
+}
+
+fn parse_param(param: &str) -> Result<(String, String), String> {
+    match param.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(format!("expected key=value, got '{}'", param)),
+    }
+}
+
+fn cli() -> Command {
+    Command::new("fzweb")
+        .about("A CLI tool to manage and open websites interactively.")
+        .after_help(
+            "Exit codes: 0 success, 1 error, 2 website not found (or invalid arguments), 3 opening failed, 4 selection aborted.",
+        )
+        .arg(
+            Arg::new("add")
+                .long("add")
+                .short('a')
+                .value_names(vec!["name", "url"])
+                .num_args(2)
+                .action(ArgAction::Append)
+                .help("Add a website with a name and URL (repeat to add several)"),
+        )
+        .arg(
+            Arg::new("yes")
+                .long("yes")
+                .short('y')
+                .action(ArgAction::SetTrue)
+                .global(true)
+                .help("Answer yes to confirmation prompts"),
+        )
+        .arg(
+            Arg::new("json")
+                .long("json")
+                .action(ArgAction::SetTrue)
+                .global(true)
+                .help("Print machine-readable JSON instead of human text"),
+        )
+        .arg(
+            Arg::new("verbose")
+                .long("verbose")
+                .short('v')
+                .action(ArgAction::Count)
+                .global(true)
+                .help("Explain what is going on on stderr; repeat for more detail"),
+        )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .value_name("template")
+                .num_args(1)
+                .global(true)
+                .value_parser(parse_format)
+                .help("Print list and search results with a template such as \"{name}: {url}\""),
+        )
+        .arg(
+            Arg::new("quiet")
+                .long("quiet")
+                .short('q')
+                .action(ArgAction::SetTrue)
+                .global(true)
+                .help("Don't print confirmation messages; errors are still shown"),
+        )
+        .arg(
+            Arg::new("dry-run")
+                .long("dry-run")
+                .action(ArgAction::SetTrue)
+                .global(true)
+                .help("Show what would change without writing the config"),
+        )
+        .arg(
+            Arg::new("config")
+                .long("config")
+                .value_name("path")
+                .num_args(1)
+                .global(true)
+                .value_parser(value_parser!(PathBuf))
+                .help("Use this config file instead of ~/.config/fzweb/config.json"),
+        )
+        .arg(
+            Arg::new("config-json")
+                .long("config-json")
+                .value_name("json")
+                .num_args(1)
+                .global(true)
+                .conflicts_with_all(["config", "profile"])
+                .help("Use this JSON config, or read it from stdin with '-'; changes are not saved"),
+        )
+        .arg(
+            Arg::new("profile")
+                .long("profile")
+                .value_name("name")
+                .num_args(1)
+                .global(true)
+                .conflicts_with("config")
+                .help("Use ~/.config/fzweb/<name>.json as the config file"),
+        )
+        .subcommand(
+            Command::new("completions")
+                .about("Print a shell completion script to stdout")
+                .arg(
+                    Arg::new("shell")
+                        .required(true)
+                        .value_parser(value_parser!(Shell))
+                        .help("The shell to generate completions for"),
+                ),
+        )
+        .subcommand(Command::new("add").about("Add a website, asking for each field"))
+        .subcommand(
+            Command::new("profiles").about("List the profiles in ~/.config/fzweb"),
+        )
+        .subcommand(Command::new("export").about("Print all saved websites as JSON to stdout"))
+        .subcommand(
+            Command::new("import")
+                .about("Merge websites from JSON on stdin, skipping names that already exist"),
+        )
+        .subcommand(
+            Command::new("import-html")
+                .about("Add the links of a browser's bookmarks.html export, tagged by folder")
+                .arg(
+                    Arg::new("file")
+                        .required(true)
+                        .value_parser(value_parser!(PathBuf))
+                        .help("Bookmarks file exported from Chrome, Firefox, Safari or Edge"),
+                ),
+        )
+        .subcommand(
+            Command::new("search")
+                .about("Print websites whose name or URL contains the query")
+                .arg(
+                    Arg::new("query")
+                        .required(true)
+                        .help("Case-insensitive text to look for"),
+                ),
+        )
+        .subcommand(
+            Command::new("show")
+                .about("Print every field of one website")
+                .arg(
+                    Arg::new("name")
+                        .required(true)
+                        .help("Name or alias of the website"),
+                ),
+        )
+        .subcommand(
+            Command::new("note")
+                .about("Edit the notes of a website in $EDITOR")
+                .arg(
+                    Arg::new("name")
+                        .required(true)
+                        .help("Name or alias of the website"),
+                ),
+        )
+        .subcommand(Command::new("count").about("Print the number of saved websites"))
+        .subcommand(
+            Command::new("stats")
+                .about("Summarize saved websites by tag and by how often they are opened"),
+        )
+        .subcommand(
+            Command::new("archive")
+                .about("Hide a website from the picker and the list without deleting it")
+                .arg(
+                    Arg::new("name")
+                        .required(true)
+                        .help("Name or alias of the website"),
+                ),
+        )
+        .subcommand(
+            Command::new("unarchive")
+                .about("Show an archived website again")
+                .arg(
+                    Arg::new("name")
+                        .required(true)
+                        .help("Name or alias of the website"),
+                ),
+        )
+        .subcommand(
+            Command::new("move")
+                .about("Move a website to another position in the list")
+                .arg(
+                    Arg::new("name")
+                        .required(true)
+                        .help("Name or alias of the website"),
+                )
+                .arg(
+                    Arg::new("position")
+                        .required(true)
+                        .value_parser(value_parser!(usize))
+                        .help("1-based position; out-of-range values go to the start or end"),
+                ),
+        )
+        .subcommand(
+            Command::new("sort")
+                .about("Reorder the saved websites permanently")
+                .arg(
+                    Arg::new("save")
+                        .long("save")
+                        .value_name("field")
+                        .required(true)
+                        .value_parser(value_parser!(SortKey))
+                        .help("Field to sort the config file by"),
+                ),
+        )
+        .subcommand(
+            Command::new("group")
+                .about("Manage named groups of websites that open together")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("add")
+                        .about("Add websites to a group, creating it if needed")
+                        .arg(Arg::new("group").required(true).help("Name of the group"))
+                        .arg(
+                            Arg::new("names")
+                                .required(true)
+                                .num_args(1..)
+                                .help("Names or aliases of the websites to add"),
+                        ),
+                )
+                .subcommand(Command::new("list").about("Print every group and its websites")),
+        )
+        .subcommand(
+            Command::new("shortcut")
+                .about("Manage URL shortcuts such as gh: for https://github.com/")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("add")
+                        .about("Make <name>:<path> in website URLs open as <url><path>")
+                        .arg(Arg::new("name").required(true).help("Name of the shortcut, without the colon"))
+                        .arg(Arg::new("url").required(true).help("URL the shortcut stands for")),
+                )
+                .subcommand(Command::new("list").about("Print every shortcut and its URL")),
+        )
+        .subcommand(
+            Command::new("meta")
+                .about("Keep notes about the config itself, such as where it came from")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("set")
+                        .about("Store a value under a key; \"\" removes the key")
+                        .arg(Arg::new("key").required(true).help("Name of the entry"))
+                        .arg(Arg::new("value").required(true).help("Text to store, or \"\" to remove it")),
+                )
+                .subcommand(
+                    Command::new("get")
+                        .about("Print the value stored under a key")
+                        .arg(Arg::new("key").required(true).help("Name of the entry")),
+                ),
+        )
+        .subcommand(
+            Command::new("config")
+                .about("Change settings stored in the config file")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("set-default-tag")
+                        .about("Only offer websites with this tag when opening; \"\" clears it")
+                        .arg(Arg::new("tag").required(true).help("The tag, or \"\" to clear it")),
+                )
+                .subcommand(
+                    Command::new("set-action")
+                        .about("Choose what opening a website does when no --copy, --url-only or --action is given")
+                        .arg(
+                            Arg::new("action")
+                                .required(true)
+                                .value_parser(value_parser!(Action))
+                                .help("open, copy or url-only"),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("schema").about("Print a JSON Schema describing the config file"),
+        )
+        .subcommand(
+            Command::new("undo")
+                .about("Swap the config with its .bak backup, reverting the last destructive change"),
+        )
+        .subcommand(
+            Command::new("path").about(
+                "Print the config file in use: --config, else --profile, else $FZWEB_CONFIG, else ~/.config/fzweb/config.json",
+            ),
+        )
+        .subcommand(
+            Command::new("doctor").about("Check the config file and environment for problems"),
+        )
+        .subcommand(
+            Command::new("check")
+                .about("Print every website whose URL isn't a valid http(s) URL, without going online"),
+        )
+        .subcommand(
+            Command::new("clear").about("Delete every website, keeping a .bak copy of the config"),
+        )
+        .subcommand(
+            Command::new("fetch-favicons")
+                .about("Download and cache the favicon of every website that has none yet"),
+        )
+        .subcommand(Command::new(SERVE_CLIPBOARD).hide(true))
+        .subcommand(
+            Command::new("prune")
+                .about("Delete websites whose URL returns an error or doesn't respond"),
+        )
+        .arg(
+            Arg::new("add-from-stdin")
+                .long("add-from-stdin")
+                .action(ArgAction::SetTrue)
+                .help("Add websites from 'name<TAB>url' or 'name,url' lines on stdin"),
+        )
+        .arg(
+            Arg::new("tag")
+                .long("tag")
+                .short('t')
+                .value_name("tag")
+                .num_args(1)
+                .action(ArgAction::Append)
+                .help("Tag the added website, or only pick from websites with this tag"),
+        )
+        .arg(
+            Arg::new("desc")
+                .long("desc")
+                .value_name("text")
+                .num_args(1)
+                .help("Describe the added website"),
+        )
+        .arg(
+            Arg::new("alias")
+                .long("alias")
+                .value_name("name")
+                .num_args(1)
+                .action(ArgAction::Append)
+                .help("Another name the added website can be found under"),
+        )
+        .arg(
+            Arg::new("command")
+                .long("command")
+                .value_name("cmd")
+                .num_args(1)
+                .help("Run this shell command instead of opening the added website; {url} is replaced by its URL"),
+        )
+        .arg(
+            Arg::new("icon")
+                .long("icon")
+                .value_name("glyph")
+                .num_args(1)
+                .help("Show this emoji or glyph before the added website's name"),
+        )
+        .arg(
+            Arg::new("force")
+                .long("force")
+                .short('f')
+                .action(ArgAction::SetTrue)
+                .help("Don't warn when the added URL is already saved under another name"),
+        )
+        .arg(
+            Arg::new("edit")
+                .long("edit")
+                .short('e')
+                .value_names(vec!["name", "url"])
+                .num_args(2)
+                .help("Change the URL of an existing website"),
+        )
+        .arg(
+            Arg::new("rename")
+                .long("rename")
+                .short('r')
+                .value_names(vec!["old", "new"])
+                .num_args(2)
+                .help("Rename an existing website"),
+        )
+        .arg(
+            Arg::new("open")
+                .long("open")
+                .short('o')
+                .action(ArgAction::Set)
+                .num_args(0..=1)
+                .value_name("name")
+                .help("Open a website in your default browser, picking one unless a name is given (ctrl-y in the picker copies its URL instead)"),
+        )
+        .arg(
+            Arg::new("copy")
+                .long("copy")
+                .short('c')
+                .action(ArgAction::SetTrue)
+                .help("Copy the website's URL to the clipboard instead of opening it"),
+        )
+        .arg(
+            Arg::new("url-only")
+                .long("url-only")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("copy")
+                .help("Print the website's URL to stdout instead of opening it"),
+        )
+        .arg(
+            Arg::new("action")
+                .long("action")
+                .value_name("action")
+                .num_args(1)
+                .value_parser(value_parser!(Action))
+                .conflicts_with_all(["copy", "url-only"])
+                .help("Open, copy or print the chosen URL, overriding default_action from the config"),
+        )
+        .arg(
+            Arg::new("browser")
+                .long("browser")
+                .short('b')
+                .value_name("app")
+                .num_args(1)
+                .help("Open with this application name or path instead of the default browser"),
+        )
+        .arg(
+            Arg::new("private")
+                .long("private")
+                .action(ArgAction::SetTrue)
+                .help("Open in a private window (Chrome, Chromium, Brave or Firefox via --browser or $BROWSER)"),
+        )
+        .arg(
+            Arg::new("new-window")
+                .long("new-window")
+                .action(ArgAction::SetTrue)
+                .help("Open in a new window rather than a tab (Chrome, Chromium, Brave or Firefox via --browser or $BROWSER)"),
+        )
+        .arg(
+            Arg::new("browser-profile")
+                .long("browser-profile")
+                .value_name("name")
+                .num_args(1)
+                .help("Open in this browser profile (Chrome, Chromium or Brave profile directory, or Firefox profile name)"),
+        )
+        .arg(
+            Arg::new("timeout")
+                .long("timeout")
+                .value_name("secs")
+                .num_args(1)
+                .default_value("1")
+                .value_parser(value_parser!(u64))
+                .help("Seconds to wait before retrying a failed open"),
+        )
+        .arg(
+            Arg::new("multi")
+                .long("multi")
+                .short('m')
+                .action(ArgAction::SetTrue)
+                .help("Pick several websites to open at once"),
+        )
+        .arg(
+            Arg::new("query")
+                .long("query")
+                .value_name("text")
+                .num_args(1)
+                .help("Start the picker with this search text"),
+        )
+        .arg(
+            Arg::new("picker")
+                .long("picker")
+                .value_name("picker")
+                .num_args(1)
+                .value_parser(value_parser!(Picker))
+                .help("Pick with skim, print the candidates (stdout) or read the choice (stdin)")
+                .long_help(
+                    "Pick with the built-in skim (the default), or split picking in two so any \
+                     fuzzy finder can be used: `stdout` prints one `name<TAB>url` line per \
+                     candidate and `stdin` reads the chosen line(s) back, for example\n\n    \
+                     fzweb -o --picker stdout | fzf | fzweb -o --picker stdin",
+                ),
+        )
+        .arg(
+            Arg::new("prompt")
+                .long("prompt")
+                .value_name("text")
+                .num_args(1)
+                .global(true)
+                .help("Prompt shown by the picker instead of skim.prompt from the config"),
+        )
+        .arg(
+            Arg::new("mouse")
+                .long("mouse")
+                .action(ArgAction::SetTrue)
+                .global(true)
+                .help("Let the picker be used with the mouse, like skim.mouse in the config"),
+        )
+        .arg(
+            Arg::new("exact")
+                .long("exact")
+                .visible_alias("first")
+                .action(ArgAction::SetTrue)
+                .requires("query")
+                .help("Open right away when only one website's name or URL contains the --query text"),
+        )
+        .arg(
+            Arg::new("param")
+                .long("param")
+                .value_name("key=value")
+                .num_args(1)
+                .action(ArgAction::Append)
+                .value_parser(parse_param)
+                .help("Append this query parameter to the URL being opened"),
+        )
+        .arg(
+            Arg::new("sort")
+                .long("sort")
+                .value_name("field")
+                .num_args(1)
+                .value_parser(value_parser!(SortKey))
+                .help("Order the list or picker by name, url, open count or last opened"),
+        )
+        .arg(
+            Arg::new("limit")
+                .long("limit")
+                .value_name("n")
+                .num_args(1)
+                .value_parser(value_parser!(usize))
+                .help("Only offer the first n websites in the picker, after sorting"),
+        )
+        .arg(
+            Arg::new("recent")
+                .long("recent")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("sort")
+                .help("Same as --sort recent: most recently opened first"),
+        )
+        .arg(
+            Arg::new("all")
+                .long("all")
+                .action(ArgAction::SetTrue)
+                .help("Open every website, or every website with the given tags; with --list, include archived ones"),
+        )
+        .arg(
+            Arg::new("repeat")
+                .long("repeat")
+                .value_name("n")
+                .num_args(1)
+                .value_parser(value_parser!(u8).range(1..=REPEAT_MAX as i64))
+                .help("Open the chosen website n times, in separate tabs (at most 20; asks above 5)"),
+        )
+        .arg(
+            Arg::new("peek")
+                .long("peek")
+                .action(ArgAction::SetTrue)
+                .help("Show the chosen URL and ask before opening it (skipped with --yes)"),
+        )
+        .arg(
+            Arg::new("random")
+                .long("random")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["all", "by-tag"])
+                .help("Open a random website, or a random one with the given tags"),
+        )
+        .arg(
+            Arg::new("least-used")
+                .long("least-used")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["all", "by-tag", "random", "sort", "recent"])
+                .help("Open the least-used website, or the least-used one with the given tags"),
+        )
+        .arg(
+            Arg::new("group")
+                .long("group")
+                .short('g')
+                .value_name("name")
+                .num_args(1)
+                .help("Open every website in this group"),
+        )
+        .arg(
+            Arg::new("by-tag")
+                .long("by-tag")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("tag")
+                .help("Pick a tag first, then a website with that tag"),
+        )
+        .arg(
+            Arg::new("create")
+                .long("create")
+                .action(ArgAction::SetTrue)
+                .help("Offer to add the typed name when nothing in the picker matches it"),
+        )
+        .arg(
+            Arg::new("list")
+                .long("list")
+                .short('l')
+                .action(ArgAction::SetTrue)
+                .help("List saved websites that aren't archived (all of them with --all)"),
+        )
+        .arg(
+            Arg::new("archived")
+                .long("archived")
+                .action(ArgAction::SetTrue)
+                .requires("list")
+                .help("List only archived websites"),
+        )
+        .arg(
+            Arg::new("del")
+                .long("del")
+                .short('d')
+                .action(ArgAction::Set)
+                .num_args(0..=1)
+                .value_name("name")
+                .help("Delete a website by name, picking one unless a name is given"),
+        )
+}
+
+/// The flags describing one added website, which would be copied onto
+/// every website when several are added at once.
+const PER_WEBSITE_FLAGS: [&str; 5] = ["tag", "desc", "alias", "command", "icon"];
+
+/// Rejects per-website flags combined with more than one `--add` pair.
+fn check_add_flags(matches: &ArgMatches) -> Result<(), clap::Error> {
+    let pairs = matches.get_many::<String>("add").map_or(0, |v| v.len() / 2);
+    match PER_WEBSITE_FLAGS.iter().find(|id| matches.contains_id(id)) {
+        Some(id) if pairs > 1 => Err(cli().error(
+            clap::error::ErrorKind::ArgumentConflict,
+            format!(
+                "--{} describes a single website and can't be used when adding several; add them one at a time",
+                id
+            ),
+        )),
+        _ => Ok(()),
+    }
+}
+
+pub fn get_args() -> MyResult<Args> {
+    let matches = cli().get_matches();
+    if let Err(e) = check_add_flags(&matches) {
+        e.exit();
+    }
+
+    Ok(Args {
+        add: matches
+            .get_many::<String>("add")
+            .map(|s| s.map(ToString::to_string).collect()),
+        add_from_stdin: matches.get_flag("add-from-stdin"),
+        add_interactive: matches.subcommand_matches("add").is_some(),
+        edit: matches
+            .get_many::<String>("edit")
+            .map(|s| s.map(ToString::to_string).collect()),
+        rename: matches
+            .get_many::<String>("rename")
+            .map(|s| s.map(ToString::to_string).collect()),
+        del: matches.get_one::<String>("del").cloned(),
+        del_pick: matches.contains_id("del") && matches.get_one::<String>("del").is_none(),
+        tags: matches
+            .get_many::<String>("tag")
+            .map(|s| s.map(ToString::to_string).collect())
+            .unwrap_or_default(),
+        desc: matches.get_one::<String>("desc").cloned(),
+        aliases: matches
+            .get_many::<String>("alias")
+            .map(|s| s.map(ToString::to_string).collect())
+            .unwrap_or_default(),
+        command: matches.get_one::<String>("command").cloned(),
+        icon: matches.get_one::<String>("icon").cloned(),
+        force: matches.get_flag("force"),
+        yes: matches.get_flag("yes"),
+        json: matches.get_flag("json"),
+        format: matches.get_one::<String>("format").cloned(),
+        quiet: matches.get_flag("quiet"),
+        verbose: matches.get_count("verbose"),
+        dry_run: matches.get_flag("dry-run"),
+        config: matches.get_one::<PathBuf>("config").cloned(),
+        profile: matches.get_one::<String>("profile").cloned(),
+        config_json: matches.get_one::<String>("config-json").cloned(),
+        profiles: matches.subcommand_matches("profiles").is_some(),
+        completions: matches
+            .subcommand_matches("completions")
+            .and_then(|m| m.get_one::<Shell>("shell").copied()),
+        export: matches.subcommand_matches("export").is_some(),
+        import: matches.subcommand_matches("import").is_some(),
+        import_html: matches
+            .subcommand_matches("import-html")
+            .and_then(|m| m.get_one::<PathBuf>("file").cloned()),
+        search: matches
+            .subcommand_matches("search")
+            .and_then(|m| m.get_one::<String>("query").cloned()),
+        show: matches
+            .subcommand_matches("show")
+            .and_then(|m| m.get_one::<String>("name").cloned()),
+        note: matches
+            .subcommand_matches("note")
+            .and_then(|m| m.get_one::<String>("name").cloned()),
+        count: matches.subcommand_matches("count").is_some(),
+        stats: matches.subcommand_matches("stats").is_some(),
+        prune: matches.subcommand_matches("prune").is_some(),
+        fetch_favicons: matches.subcommand_matches("fetch-favicons").is_some(),
+        path: matches.subcommand_matches("path").is_some(),
+        undo: matches.subcommand_matches("undo").is_some(),
+        schema: matches.subcommand_matches("schema").is_some(),
+        doctor: matches.subcommand_matches("doctor").is_some(),
+        check: matches.subcommand_matches("check").is_some(),
+        clear: matches.subcommand_matches("clear").is_some(),
+        set_default_tag: matches
+            .subcommand_matches("config")
+            .and_then(|m| m.subcommand_matches("set-default-tag"))
+            .and_then(|m| m.get_one::<String>("tag").cloned()),
+        set_action: matches
+            .subcommand_matches("config")
+            .and_then(|m| m.subcommand_matches("set-action"))
+            .and_then(|m| m.get_one::<Action>("action").copied()),
+        serve_clipboard: matches.subcommand_matches(SERVE_CLIPBOARD).is_some(),
+        group_add: matches
+            .subcommand_matches("group")
+            .and_then(|m| m.subcommand_matches("add"))
+            .and_then(|m| {
+                Some((
+                    m.get_one::<String>("group")?.clone(),
+                    m.get_many::<String>("names")?.cloned().collect(),
+                ))
+            }),
+        group_list: matches
+            .subcommand_matches("group")
+            .and_then(|m| m.subcommand_matches("list"))
+            .is_some(),
+        shortcut_add: matches
+            .subcommand_matches("shortcut")
+            .and_then(|m| m.subcommand_matches("add"))
+            .and_then(|m| {
+                Some((
+                    m.get_one::<String>("name")?.clone(),
+                    m.get_one::<String>("url")?.clone(),
+                ))
+            }),
+        shortcut_list: matches
+            .subcommand_matches("shortcut")
+            .and_then(|m| m.subcommand_matches("list"))
+            .is_some(),
+        meta_set: matches
+            .subcommand_matches("meta")
+            .and_then(|m| m.subcommand_matches("set"))
+            .and_then(|m| {
+                Some((
+                    m.get_one::<String>("key")?.clone(),
+                    m.get_one::<String>("value")?.clone(),
+                ))
+            }),
+        meta_get: matches
+            .subcommand_matches("meta")
+            .and_then(|m| m.subcommand_matches("get"))
+            .and_then(|m| m.get_one::<String>("key").cloned()),
+        archive: matches
+            .subcommand_matches("archive")
+            .and_then(|m| m.get_one::<String>("name").cloned()),
+        unarchive: matches
+            .subcommand_matches("unarchive")
+            .and_then(|m| m.get_one::<String>("name").cloned()),
+        sort_save: matches
+            .subcommand_matches("sort")
+            .and_then(|m| m.get_one::<SortKey>("save").copied()),
+        move_to: matches.subcommand_matches("move").and_then(|m| {
+            Some((
+                m.get_one::<String>("name")?.clone(),
+                *m.get_one::<usize>("position")?,
+            ))
+        }),
+        open: matches.contains_id("open"),
+        open_name: matches.get_one::<String>("open").cloned(),
+        copy: matches.get_flag("copy"),
+        url_only: matches.get_flag("url-only"),
+        action: matches.get_one::<Action>("action").copied(),
+        browser: matches.get_one::<String>("browser").cloned(),
+        private: matches.get_flag("private"),
+        new_window: matches.get_flag("new-window"),
+        browser_profile: matches.get_one::<String>("browser-profile").cloned(),
+        timeout: matches.get_one::<u64>("timeout").copied().unwrap_or(1),
+        multi: matches.get_flag("multi"),
+        query: matches.get_one::<String>("query").cloned(),
+        params: matches
+            .get_many::<(String, String)>("param")
+            .map(|p| p.cloned().collect())
+            .unwrap_or_default(),
+        sort: if matches.get_flag("recent") {
+            Some(SortKey::Recent)
+        } else if matches.get_flag("least-used") {
+            Some(SortKey::Neglected)
+        } else {
+            matches.get_one::<SortKey>("sort").copied()
+        },
+        limit: matches.get_one::<usize>("limit").copied(),
+        prompt: matches.get_one::<String>("prompt").cloned(),
+        mouse: matches.get_flag("mouse"),
+        picker: matches
+            .get_one::<Picker>("picker")
+            .copied()
+            .unwrap_or_default(),
+        all: matches.get_flag("all"),
+        random: matches.get_flag("random"),
+        least_used: matches.get_flag("least-used"),
+        peek: matches.get_flag("peek"),
+        exact: matches.get_flag("exact"),
+        repeat: matches.get_one::<u8>("repeat").map_or(1, |&n| n.into()),
+        group: matches.get_one::<String>("group").cloned(),
+        by_tag: matches.get_flag("by-tag"),
+        create: matches.get_flag("create"),
+        list: matches.get_flag("list"),
+        archived: matches.get_flag("archived"),
+    })
+}
+
+struct PickerItem {
+    name: String,
+    text: String,
+    // shown under the list for the highlighted item
+    preview: String,
+}
+
+impl PickerItem {
+    fn new(website: &Website) -> Self {
+        let mut text = website.label();
+        if !website.aliases.is_empty() {
+            text.push_str(&format!(" [{}]", website.aliases.join(", ")));
+        }
+        PickerItem {
+            name: website.name.clone(),
+            text,
+            preview: website.url.clone(),
+        }
+    }
+
+    fn tag(tag: &str, count: usize) -> Self {
+        PickerItem {
+            name: tag.to_string(),
+            text: tag.to_string(),
+            preview: format!("{} website(s)", count),
+        }
+    }
+}
+
+impl SkimItem for PickerItem {
+    fn text(&self) -> Cow<'_, str> {
+        Cow::Borrowed(&self.text)
+    }
+
+    fn output(&self) -> Cow<'_, str> {
+        Cow::Borrowed(&self.name)
+    }
+
+    fn preview(&self, _context: PreviewContext) -> ItemPreview {
+        ItemPreview::Text(self.preview.clone())
+    }
+}
+
+struct PickerOptions<'a> {
+    multi: bool,
+    skim: &'a SkimConfig,
+    query: Option<&'a str>,
+    // offer ctrl-y to copy the picked URLs instead of opening them
+    copy_key: bool,
+}
+
+/// The names chosen in the picker and whether they were accepted with the
+/// copy key rather than Enter.
+struct Picked {
+    names: Vec<String>,
+    copy: bool,
+}
+
+/// Lets the user choose among websites. The CLI uses skim; anything else
+/// (a scripted stand-in, for instance) can be passed to `run_with`.
+trait Selector {
+    fn pick(&self, items: Vec<PickerItem>, options: &PickerOptions) -> MyResult<Picked>;
+}
+
+struct SkimSelector;
+
+impl Selector for SkimSelector {
+    fn pick(&self, items: Vec<PickerItem>, options: &PickerOptions) -> MyResult<Picked> {
+        let (bind, header) = if options.copy_key {
+            (
+                vec![String::from("ctrl-y:accept(ctrl-y)")],
+                Some(String::from("enter: open  ctrl-y: copy URL")),
+            )
+        } else {
+            (Vec::new(), None)
+        };
+        let skim_options = SkimOptionsBuilder::default()
+            .bind(bind)
+            .header(header)
+            .query(options.query.map(ToString::to_string))
+            .height(options.skim.height.clone())
+            .reverse(options.skim.reverse)
+            .prompt(options.skim.prompt.clone())
+            .multi(options.multi)
+            .no_multi(!options.multi)
+            .no_mouse(!options.skim.mouse)
+            .preview(Some(String::new()))
+            .preview_window(String::from("down:1"))
+            .build()
+            .unwrap();
+
+        let (tx, rx): (SkimItemSender, SkimItemReceiver) = unbounded();
+        for item in items {
+            let _ = tx.send(Arc::new(item));
+        }
+        drop(tx);
+
+        let output = Skim::run_with(&skim_options, Some(rx)).ok_or(FzwebError::SelectionAborted)?;
+
+        if output.is_abort {
+            return Err(FzwebError::SelectionAborted);
+        }
+        // accepted with nothing matching the typed query
+        if output.selected_items.is_empty() && !output.query.is_empty() {
+            return Err(FzwebError::NoMatch(output.query));
+        }
+        Ok(Picked {
+            names: output
+                .selected_items
+                .iter()
+                .map(|s| s.output().to_string())
+                .collect(),
+            copy: matches!(&output.final_event, Event::EvActAccept(Some(key)) if key == "ctrl-y"),
+        })
+    }
+}
+
+/// Reads the choice of an outside picker from stdin: one line per pick,
+/// each the name alone or a `name<TAB>...` line as printed by [`emit`].
+struct StdinSelector;
+
+impl Selector for StdinSelector {
+    fn pick(&self, items: Vec<PickerItem>, _options: &PickerOptions) -> MyResult<Picked> {
+        let mut names = Vec::new();
+        for line in io::stdin().lock().lines() {
+            let line = line?;
+            let name = line.split('\t').next().unwrap_or_default().trim();
+            if name.is_empty() {
+                continue;
+            }
+            if !items.iter().any(|item| item.name == name) {
+                return Err(FzwebError::NoMatch(name.to_string()));
+            }
+            names.push(name.to_string());
+        }
+        Ok(Picked { names, copy: false })
+    }
+}
+
+/// Prints the candidates for an outside picker, one `name<TAB>preview`
+/// line each.
+fn emit(items: &[PickerItem]) {
+    for item in items {
+        println!("{}\t{}", item.name, item.preview);
+    }
+}
+
+fn select(
+    selector: &dyn Selector,
+    items: Vec<PickerItem>,
+    skim: &SkimConfig,
+    query: Option<&str>,
+) -> MyResult<String> {
+    let options = PickerOptions {
+        multi: false,
+        skim,
+        query,
+        copy_key: false,
+    };
+    selector
+        .pick(items, &options)?
+        .names
+        .into_iter()
+        .next()
+        .ok_or(FzwebError::SelectionAborted)
+}
+
+/// Picks the website(s) to open, with ctrl-y available to copy instead.
+fn select_to_open(
+    selector: &dyn Selector,
+    items: Vec<PickerItem>,
+    skim: &SkimConfig,
+    options: &OpenOptions,
+) -> MyResult<Picked> {
+    let options = PickerOptions {
+        multi: options.multi,
+        skim,
+        query: options.query.as_deref(),
+        copy_key: options.action == Action::Open,
+    };
+    let mut picked = selector.pick(items, &options)?;
+    if !options.multi {
+        picked.names.truncate(1);
+    }
+    if picked.names.is_empty() {
+        return Err(FzwebError::SelectionAborted);
+    }
+    Ok(picked)
+}
+
+pub fn run(args: Args) -> MyResult<()> {
+    match args.picker {
+        Picker::Stdin => run_with(args, &StdinSelector),
+        Picker::Skim | Picker::Stdout => run_with(args, &SkimSelector),
+    }
+}
+
+fn run_with(args: Args, selector: &dyn Selector) -> MyResult<()> {
+    // started by copy_to_clipboard
+    if args.serve_clipboard {
+        return serve_clipboard();
+    }
+
+    // completions
+    if let Some(shell) = args.completions {
+        let mut command = cli();
+        let name = command.get_name().to_string();
+        generate(shell, &mut command, name, &mut io::stdout());
+        return Ok(());
+    }
+
+    // profiles
+    if args.profiles {
+        let profiles = Config::profiles()?;
+        if args.json {
+            println!("{}", serde_json::to_string(&profiles)?);
+        } else {
+            for profile in profiles {
+                println!("{}", profile);
+            }
+        }
+        return Ok(());
+    }
+
+    // schema
+    if args.schema {
+        println!("{}", serde_json::to_string_pretty(&schema_for!(Config))?);
+        return Ok(());
+    }
+
+    let config_path = Config::resolve_path(args.config.as_deref(), args.profile.as_deref());
+
+    // path
+    if args.path {
+        if args.json {
+            println!("{}", serde_json::json!({ "path": config_path }));
+        } else {
+            println!("{}", config_path.display());
+        }
+        return Ok(());
+    }
+
+    // doctor
+    if args.doctor {
+        return doctor(&config_path);
+    }
+
+    let report = Report {
+        json: args.json,
+        dry_run: args.dry_run,
+        quiet: args.quiet,
+        verbose: args.verbose,
+    };
+
+    // undo
+    if args.undo {
+        return Config::undo(&config_path, args.yes, args.dry_run)?.report(report);
+    }
+
+    let mut config = match args.config_json {
+        Some(json) if json == "-" => io::read_to_string(io::stdin())?.parse()?,
+        Some(json) => json.parse()?,
+        None if args.changes_config() && !args.dry_run => Config::load_locked(&config_path)?,
+        None => Config::load(&config_path)?,
+    };
+    config.report = report;
+    if config.path.as_os_str().is_empty() {
+        report.log(1, "config: read from --config-json");
+    } else {
+        report.log(1, format_args!("config: {}", config.path.display()));
+    }
+    report.log(
+        1,
+        format_args!(
+            "loaded {} website(s), config version {}",
+            config.websites.len(),
+            config.version
+        ),
+    );
+    config.migrate()?;
+    config.dedupe()?;
+    if args.prompt.is_some() || args.mouse {
+        let mut skim = config.skim.clone();
+        skim.prompt = args.prompt.unwrap_or(skim.prompt);
+        skim.mouse |= args.mouse;
+        config.cli_skim = Some(skim);
+    }
+
+    // export
+    if args.export {
+        println!("{}", config.to_json_string());
+        return Ok(());
+    }
+
+    // import
+    if args.import {
+        let imported: Config = serde_json::from_reader(io::stdin()).map_err(FzwebError::Import)?;
+        return config.merge(imported)?.report(report);
+    }
+
+    // import html
+    if let Some(path) = args.import_html {
+        return config.import_bookmarks(&path)?.report(report);
+    }
+
+    // search
+    if let Some(query) = args.search {
+        let matches = config.search(&query);
+        if matches.is_empty() {
+            return Err(FzwebError::NoMatch(query));
+        }
+        if args.json {
+            println!("{}", serde_json::to_string(&matches)?);
+            return Ok(());
+        }
+        if let Some(template) = &args.format {
+            print_formatted(&matches, template);
+            return Ok(());
+        }
+        let rows: Vec<Row> = matches
+            .iter()
+            .map(|w| (w.name.clone(), w.url.as_str(), None))
+            .collect();
+        print_rows(&rows);
+        return Ok(());
+    }
+
+    // show
+    if let Some(name) = args.show {
+        let website = config.find(&name).ok_or(FzwebError::NotFound(name))?;
+        if args.json {
+            println!("{}", serde_json::to_string(website)?);
+        } else {
+            println!("{}", website);
+        }
+        return Ok(());
+    }
+
+    // count
+    if args.count {
+        println!("{}", config.count());
+        return Ok(());
+    }
+
+    // stats
+    if args.stats {
+        let stats = config.stats();
+        if args.json {
+            println!("{}", serde_json::to_string(&stats)?);
+        } else {
+            println!("{}", stats);
+        }
+        return Ok(());
+    }
+
+    // clear
+    if args.clear {
+        return config.clear(args.yes)?.report(report);
+    }
+
+    // fetch favicons
+    if args.fetch_favicons {
+        return config.fetch_favicons()?.report(report);
+    }
+
+    // prune
+    if args.prune {
+        return config.prune(args.yes)?.report(report);
+    }
+
+    // init
+    if config.websites.is_empty() {
+        config.save()?;
+    }
+
+    // add
+    if let Some(add_site_info) = args.add {
+        let several = add_site_info.len() > 2;
+        for pair in add_site_info.chunks_exact(2) {
+            let website = Website {
+                name: pair[0].clone(),
+                url: pair[1].clone(),
+                tags: args.tags.clone(),
+                description: args.desc.clone(),
+                aliases: args.aliases.clone(),
+                command: args.command.clone(),
+                icon: args.icon.clone(),
+                ..Website::default()
+            };
+            match config.add_website(website, args.force) {
+                Ok(outcome) => outcome.report(report)?,
+                Err(
+                    e @ (FzwebError::Duplicate(_)
+                    | FzwebError::InvalidUrl(_)
+                    | FzwebError::EmptyField(_)),
+                ) if several => {
+                    eprintln!("Skipped '{}': {}", pair[0], e)
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    // add interactively
+    if args.add_interactive {
+        config.add_interactively(args.force)?.report(report)?;
+    }
+
+    // add from stdin
+    if args.add_from_stdin {
+        config
+            .add_from_reader(io::stdin().lock(), args.force)?
+            .report(report)?;
+    }
+
+    // edit
+    if let Some(edit_site_info) = args.edit
+        && let (Some(name), Some(url)) = (edit_site_info.first(), edit_site_info.get(1))
+    {
+        config
+            .edit_website(name.clone(), url.clone())?
+            .report(report)?;
+    }
+
+    // rename
+    if let Some(rename_site_info) = args.rename
+        && let (Some(old), Some(new)) = (rename_site_info.first(), rename_site_info.get(1))
+    {
+        config
+            .rename_website(old.clone(), new.clone())?
+            .report(report)?;
+    }
+
+    // note
+    if let Some(name) = args.note {
+        config.edit_notes(&name)?.report(report)?;
+    }
+
+    // group
+    if let Some((group, names)) = args.group_add {
+        config.add_to_group(group, names)?.report(report)?;
+    }
+    if args.group_list {
+        config.list_groups(args.json)?;
+    }
+
+    // shortcut
+    if let Some((name, url)) = args.shortcut_add {
+        config.add_shortcut(name, url)?.report(report)?;
+    }
+    if args.shortcut_list {
+        config.list_shortcuts(args.json)?;
+    }
+
+    // check
+    if args.check {
+        config.check_urls(args.json)?;
+    }
+
+    // meta
+    if let Some((key, value)) = args.meta_set {
+        config.set_meta(key, value)?.report(report)?;
+    }
+    if let Some(key) = &args.meta_get {
+        println!("{}", config.get_meta(key)?);
+    }
+
+    // config
+    if let Some(tag) = args.set_default_tag {
+        config.set_default_tag(tag)?.report(report)?;
+    }
+    if let Some(action) = args.set_action {
+        config.set_default_action(action)?.report(report)?;
+    }
+
+    // archive
+    if let Some(name) = args.archive {
+        config.set_archived(&name, true)?.report(report)?;
+    }
+    if let Some(name) = args.unarchive {
+        config.set_archived(&name, false)?.report(report)?;
+    }
+
+    // move
+    if let Some((name, position)) = args.move_to {
+        config.move_website(name, position)?.report(report)?;
+    }
+
+    // sort
+    if let Some(key) = args.sort_save {
+        config.sort_websites(key, args.yes)?.report(report)?;
+    }
+
+    // del
+    if let Some(delete_site_info) = args.del {
+        config
+            .remove_website(delete_site_info, args.yes)?
+            .report(report)?;
+    } else if args.del_pick {
+        let items: Vec<PickerItem> = config.websites.iter().map(PickerItem::new).collect();
+        if args.picker == Picker::Stdout {
+            emit(&items);
+        } else {
+            let name = select(selector, items, config.skim(), args.query.as_deref())?;
+            config.remove_website(name, args.yes)?.report(report)?;
+        }
+    }
+
+    // list
+    if args.list {
+        let archived = if args.archived {
+            Some(true)
+        } else if args.all {
+            None
+        } else {
+            Some(false)
+        };
+        config.list_websites(args.sort, archived, args.json, args.format.as_deref())?;
+    }
+
+    // open
+    if args.open
+        || args.copy
+        || args.url_only
+        || args.by_tag
+        || args.random
+        || args.least_used
+        || args.action.is_some()
+        || args.group.is_some()
+    {
+        // an explicit --tag or --all overrides the default tag
+        let tags = match &config.default_tag {
+            Some(tag) if args.tags.is_empty() && !args.all && !args.by_tag => vec![tag.clone()],
+            _ => args.tags,
+        };
+        let options = OpenOptions {
+            name: args.open_name,
+            tags,
+            multi: args.multi,
+            action: if args.copy {
+                Action::Copy
+            } else if args.url_only {
+                Action::Print
+            } else {
+                args.action.or(config.default_action).unwrap_or_default()
+            },
+            browser: args.browser,
+            query: args.query,
+            params: args.params,
+            sort: args.sort,
+            limit: args.limit,
+            private: args.private,
+            new_window: args.new_window,
+            browser_profile: args.browser_profile,
+            retry_delay: Duration::from_secs(args.timeout),
+            all: args.all,
+            random: args.random,
+            least_used: args.least_used,
+            peek: args.peek,
+            exact: args.exact,
+            repeat: args.repeat,
+            group: args.group,
+            by_tag: args.by_tag,
+            create: args.create,
+            yes: args.yes,
+            emit: args.picker == Picker::Stdout,
+        };
+        config.open_website(&options, selector)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh, empty directory for one test, named after it so parallel
+    /// tests never share files.
+    fn temp_dir(test: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("fzweb-test-{}-{}", process::id(), test));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// A config saved at `dir/config.json` holding one website per
+    /// `(name, url)` pair, in order.
+    fn config_with(dir: &Path, websites: &[(&str, &str)]) -> Config {
+        let mut config = Config::load(&dir.join("config.json")).unwrap();
+        for (name, url) in websites {
+            config.add(Website::new(*name, *url)).unwrap();
+        }
+        config
+    }
+
+    fn names(config: &Config) -> Vec<&str> {
+        config.list().iter().map(Website::name).collect()
+    }
+
+    /// Picks the website named `pick` whatever is offered, or aborts like
+    /// escaping the picker when it is `None`.
+    struct FakeSelector {
+        pick: Option<&'static str>,
+    }
+
+    impl Selector for FakeSelector {
+        fn pick(&self, items: Vec<PickerItem>, _options: &PickerOptions) -> MyResult<Picked> {
+            let name = self.pick.ok_or(FzwebError::SelectionAborted)?;
+            assert!(items.iter().any(|item| item.output() == name));
+            Ok(Picked {
+                names: vec![name.to_string()],
+                copy: false,
+            })
+        }
+    }
+
+    /// Arguments for `run_with` working on the config at `path`.
+    fn args(path: &Path) -> Args {
+        Args {
+            config: Some(path.to_path_buf()),
+            quiet: true,
+            ..Args::default()
+        }
+    }
+
+    #[test]
+    fn edit_replaces_url_in_place() {
+        let dir = temp_dir("edit_replaces_url_in_place");
+        let mut config = config_with(
+            &dir,
+            &[
+                ("a", "https://a.com"),
+                ("b", "https://b.com"),
+                ("c", "https://c.com"),
+            ],
+        );
+
+        config.edit("b", "https://b.org/new").unwrap();
+
+        assert_eq!(names(&config), ["a", "b", "c"]);
+        let reloaded = Config::load(&dir.join("config.json")).unwrap();
+        assert_eq!(reloaded.list()[1].url(), "https://b.org/new");
+    }
+
+    #[test]
+    fn edit_unknown_name_writes_nothing() {
+        let dir = temp_dir("edit_unknown_name_writes_nothing");
+        let mut config = config_with(&dir, &[("a", "https://a.com")]);
+        let before = fs::read_to_string(dir.join("config.json")).unwrap();
+
+        let result = config.edit("nope", "https://b.com");
+
+        assert!(matches!(result, Err(FzwebError::NotFound(name)) if name == "nope"));
+        assert_eq!(fs::read_to_string(dir.join("config.json")).unwrap(), before);
+    }
+
+    #[test]
+    fn fzweb_config_overrides_default_path() {
+        let path = temp_dir("fzweb_config_overrides_default_path").join("nested/sites.json");
+        // no other test reads FZWEB_CONFIG
+        unsafe { env::set_var("FZWEB_CONFIG", &path) };
+        let default_path = Config::default_path();
+        unsafe { env::remove_var("FZWEB_CONFIG") };
+        assert_eq!(default_path, path);
+
+        let mut config = Config::load(&default_path).unwrap();
+        config.add(Website::new("a", "https://a.com")).unwrap();
+
+        let reloaded = Config::load(&path).unwrap();
+        assert_eq!(names(&reloaded), ["a"]);
+    }
+
+    #[test]
+    fn save_leaves_no_temp_file() {
+        let dir = temp_dir("save_leaves_no_temp_file");
+        let config = config_with(&dir, &[("a", "https://a.com")]);
+
+        config.save().unwrap();
+
+        assert!(dir.join("config.json").is_file());
+        assert!(!dir.join("config.json.tmp").exists());
+    }
+
+    #[test]
+    fn rename_keeps_url_and_updates_groups() {
+        let dir = temp_dir("rename_keeps_url_and_updates_groups");
+        let mut config = config_with(&dir, &[("a", "https://a.com"), ("b", "https://b.com")]);
+        config
+            .add_to_group(String::from("g"), vec![String::from("a")])
+            .unwrap();
+
+        let outcome = config
+            .rename_website(String::from("a"), String::from("c"))
+            .unwrap();
+
+        assert!(matches!(outcome, Outcome::Renamed { from, to } if from == "a" && to == "c"));
+        let reloaded = Config::load(&dir.join("config.json")).unwrap();
+        assert_eq!(names(&reloaded), ["c", "b"]);
+        assert_eq!(reloaded.list()[0].url(), "https://a.com");
+        assert_eq!(reloaded.groups["g"], ["c"]);
+    }
+
+    #[test]
+    fn rename_rejects_taken_names() {
+        let dir = temp_dir("rename_rejects_taken_names");
+        let mut config = config_with(&dir, &[("a", "https://a.com"), ("b", "https://b.com")]);
+
+        for new in ["b", "B"] {
+            let result = config.rename_website(String::from("a"), String::from(new));
+            assert!(matches!(result, Err(FzwebError::Duplicate(name)) if name == new));
+        }
+        assert_eq!(names(&config), ["a", "b"]);
+
+        // changing only the case of its own name is not a collision
+        config
+            .rename_website(String::from("a"), String::from("A"))
+            .unwrap();
+        assert_eq!(names(&config), ["A", "b"]);
+    }
+
+    #[test]
+    fn validate_url_accepts_http_urls() {
+        assert_eq!(
+            validate_url("https://example.com/path?q=1").as_deref(),
+            Some("https://example.com/path?q=1")
+        );
+        assert_eq!(
+            validate_url("http://example.com").as_deref(),
+            Some("http://example.com")
+        );
+    }
+
+    #[test]
+    fn validate_url_adds_missing_scheme() {
+        assert_eq!(
+            validate_url("example.com").as_deref(),
+            Some("https://example.com")
+        );
+    }
+
+    #[test]
+    fn validate_url_rejects_garbage() {
+        assert_eq!(validate_url("not a url"), None);
+        assert_eq!(validate_url("ftp://example.com"), None);
+        assert_eq!(validate_url(""), None);
+    }
+
+    #[test]
+    fn description_survives_save_and_load() {
+        let dir = temp_dir("description_survives_save_and_load");
+        let mut config = config_with(&dir, &[]);
+        config
+            .add(Website {
+                description: Some(String::from("code review")),
+                ..Website::new("a", "https://a.com")
+            })
+            .unwrap();
+
+        let reloaded = Config::load(&dir.join("config.json")).unwrap();
+        assert_eq!(reloaded.list()[0].description(), Some("code review"));
+    }
+
+    #[test]
+    fn same_url_under_another_name_warns_unless_forced() {
+        let dir = temp_dir("same_url_under_another_name_warns_unless_forced");
+        let mut config = config_with(&dir, &[("a", "https://example.com/")]);
+        config
+            .add_website(Website::new("b", "HTTPS://Example.com"), false)
+            .unwrap();
+        let added = &config.list()[1];
+
+        assert_eq!(
+            config.duplicate_url_warning(added, false).as_deref(),
+            Some("Note: this URL is already saved as 'a'.")
+        );
+        assert_eq!(config.duplicate_url_warning(added, true), None);
+
+        config
+            .add_website(Website::new("c", "https://example.org"), false)
+            .unwrap();
+        assert_eq!(config.duplicate_url_warning(&config.list()[2], false), None);
+    }
+
+    #[test]
+    fn delete_without_terminal_refuses() {
+        // the prompt would be answered by whoever runs the tests
+        if io::stdin().is_terminal() {
+            return;
+        }
+        let dir = temp_dir("delete_without_terminal_refuses");
+        let mut config = config_with(&dir, &[("a", "https://a.com")]);
+
+        assert!(matches!(
+            confirm("Delete?"),
+            Err(FzwebError::NotInteractive)
+        ));
+        let result = config.remove_website(String::from("a"), false);
+
+        assert!(matches!(result, Err(FzwebError::NotInteractive)));
+        assert_eq!(names(&config), ["a"]);
+        let reloaded = Config::load(&dir.join("config.json")).unwrap();
+        assert_eq!(names(&reloaded), ["a"]);
+    }
+
+    #[test]
+    fn aliases_survive_save_and_load() {
+        let dir = temp_dir("aliases_survive_save_and_load");
+        let mut config = config_with(&dir, &[]);
+        config
+            .add(Website {
+                aliases: vec![String::from("jira"), String::from("tickets")],
+                ..Website::new("tracker", "https://tracker.example.com")
+            })
+            .unwrap();
+
+        let reloaded = Config::load(&dir.join("config.json")).unwrap();
+        assert_eq!(reloaded.list()[0].aliases(), ["jira", "tickets"]);
+        assert_eq!(reloaded.find("tickets").map(Website::name), Some("tracker"));
+    }
+
+    #[test]
+    fn add_rejects_a_name_taken_by_an_alias() {
+        let dir = temp_dir("add_rejects_a_name_taken_by_an_alias");
+        let mut config = config_with(&dir, &[]);
+        config
+            .add(Website {
+                aliases: vec![String::from("jira")],
+                ..Website::new("tracker", "https://tracker.example.com")
+            })
+            .unwrap();
+
+        let result = config.add(Website::new("Jira", "https://jira.example.com"));
+
+        assert!(matches!(result, Err(FzwebError::Duplicate(name)) if name == "Jira"));
+        assert_eq!(names(&config), ["tracker"]);
+    }
+
+    #[test]
+    fn migrate_upgrades_unversioned_configs() {
+        let dir = temp_dir("migrate_upgrades_unversioned_configs");
+        let path = dir.join("config.json");
+        fs::write(
+            &path,
+            r#"{"websites": [{"name": "a", "url": "https://a.com"}]}"#,
+        )
+        .unwrap();
+
+        let mut config = Config::load(&path).unwrap();
+        assert_eq!(config.version, 0);
+        config.migrate().unwrap();
+
+        assert_eq!(config.version, CONFIG_VERSION);
+        let reloaded = Config::load(&path).unwrap();
+        assert_eq!(reloaded.version, CONFIG_VERSION);
+        assert_eq!(names(&reloaded), ["a"]);
+    }
+
+    #[test]
+    fn backup_keeps_the_file_from_before_the_write() {
+        let dir = temp_dir("backup_keeps_the_file_from_before_the_write");
+        let mut config = config_with(&dir, &[("a", "https://a.com"), ("b", "https://b.com")]);
+        let before = fs::read_to_string(dir.join("config.json")).unwrap();
+
+        config.remove("a").unwrap();
+
+        let backup = fs::read_to_string(dir.join("config.json.bak")).unwrap();
+        assert_eq!(backup, before);
+        assert_ne!(fs::read_to_string(dir.join("config.json")).unwrap(), before);
+    }
+
+    #[test]
+    fn run_adds_every_pair() {
+        let path = temp_dir("run_adds_every_pair").join("config.json");
+        let add = ["a", "https://a.com", "b", "https://b.com"];
+
+        let args = Args {
+            add: Some(add.iter().map(ToString::to_string).collect()),
+            ..args(&path)
+        };
+        run_with(args, &FakeSelector { pick: None }).unwrap();
+
+        assert_eq!(names(&Config::load(&path).unwrap()), ["a", "b"]);
+    }
+
+    #[test]
+    fn run_deletes_the_picked_website() {
+        let dir = temp_dir("run_deletes_the_picked_website");
+        config_with(&dir, &[("a", "https://a.com"), ("b", "https://b.com")]);
+        let path = dir.join("config.json");
+
+        let args = Args {
+            del_pick: true,
+            yes: true,
+            ..args(&path)
+        };
+        run_with(args, &FakeSelector { pick: Some("b") }).unwrap();
+
+        assert_eq!(names(&Config::load(&path).unwrap()), ["a"]);
+    }
+
+    #[test]
+    fn run_deletes_nothing_when_the_picker_is_aborted() {
+        let dir = temp_dir("run_deletes_nothing_when_the_picker_is_aborted");
+        config_with(&dir, &[("a", "https://a.com")]);
+        let path = dir.join("config.json");
+
+        let args = Args {
+            del_pick: true,
+            yes: true,
+            ..args(&path)
+        };
+        let result = run_with(args, &FakeSelector { pick: None });
+
+        assert!(matches!(result, Err(FzwebError::SelectionAborted)));
+        assert_eq!(names(&Config::load(&path).unwrap()), ["a"]);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn run_opens_the_picked_website() {
+        let dir = temp_dir("run_opens_the_picked_website");
+        config_with(&dir, &[("a", "https://a.com"), ("b", "https://b.com")]);
+        let path = dir.join("config.json");
+
+        let args = Args {
+            open: true,
+            // a "browser" that accepts the URL and does nothing
+            browser: Some(String::from("true")),
+            ..args(&path)
+        };
+        run_with(args, &FakeSelector { pick: Some("b") }).unwrap();
+
+        let config = Config::load(&path).unwrap();
+        let counts: Vec<u32> = config.list().iter().map(|w| w.open_count).collect();
+        assert_eq!(counts, [0, 1]);
+    }
+
+    #[test]
+    fn prune_refuses_when_nothing_answers() {
+        let dir = temp_dir("prune_refuses_when_nothing_answers");
+        let mut config = config_with(
+            &dir,
+            &[
+                // nothing listens on port 1, as when the network is down
+                ("local", "http://127.0.0.1:1/"),
+                ("variable", "https://$FZWEB_TEST_UNSET_HOST/"),
+                ("placeholder", "https://example.com/search?q={query}"),
+            ],
+        );
+
+        let result = config.prune(true);
+
+        assert!(matches!(result, Err(FzwebError::Offline(1))));
+        assert_eq!(names(&config), ["local", "variable", "placeholder"]);
+    }
+
+    #[test]
+    fn move_reorders_and_clamps() {
+        let dir = temp_dir("move_reorders_and_clamps");
+        let mut config = config_with(
+            &dir,
+            &[
+                ("a", "https://a.com"),
+                ("b", "https://b.com"),
+                ("c", "https://c.com"),
+            ],
+        );
+
+        config.move_website(String::from("c"), 2).unwrap();
+        assert_eq!(names(&config), ["a", "c", "b"]);
+
+        let outcome = config.move_website(String::from("b"), 0).unwrap();
+        assert!(matches!(outcome, Outcome::Moved { position: 1, .. }));
+        assert_eq!(names(&config), ["b", "a", "c"]);
+
+        let outcome = config.move_website(String::from("b"), 99).unwrap();
+        assert!(matches!(outcome, Outcome::Moved { position: 3, .. }));
+        assert_eq!(names(&config), ["a", "c", "b"]);
+
+        let reloaded = Config::load(&dir.join("config.json")).unwrap();
+        assert_eq!(names(&reloaded), ["a", "c", "b"]);
+    }
+
+    #[test]
+    fn dedupe_keeps_the_first_of_each_name() {
+        let dir = temp_dir("dedupe_keeps_the_first_of_each_name");
+        let path = dir.join("config.json");
+        fs::write(
+            &path,
+            r#"{"version": 1, "websites": [
+                {"name": "a", "url": "https://first.com"},
+                {"name": "b", "url": "https://b.com"},
+                {"name": "a", "url": "https://second.com"}
+            ]}"#,
+        )
+        .unwrap();
+
+        let mut config = Config::load(&path).unwrap();
+        config.dedupe().unwrap();
+
+        assert_eq!(names(&config), ["a", "b"]);
+        assert_eq!(config.list()[0].url(), "https://first.com");
+        assert_eq!(names(&Config::load(&path).unwrap()), ["a", "b"]);
+    }
+
+    #[test]
+    fn url_only_fails_when_no_website_has_the_tag() {
+        let dir = temp_dir("url_only_fails_when_no_website_has_the_tag");
+        config_with(&dir, &[("a", "https://a.com")]);
+
+        let args = Args {
+            open: true,
+            url_only: true,
+            tags: vec![String::from("nope")],
+            ..args(&dir.join("config.json"))
+        };
+        let result = run_with(args, &FakeSelector { pick: None });
+
+        assert!(matches!(result, Err(FzwebError::NoTagged(tags)) if tags == ["nope"]));
+    }
+
+    #[test]
+    fn normalize_url_adds_a_scheme() {
+        assert_eq!(normalize_url("example.com"), "https://example.com");
+        assert_eq!(normalize_url("example.com/"), "https://example.com");
+        assert_eq!(normalize_url("http://example.com"), "http://example.com");
+    }
+
+    #[test]
+    fn normalize_url_lowercases_the_host_only() {
+        assert_eq!(
+            normalize_url("HTTP://User@Example.COM/Path/To?Q=Mixed#Frag"),
+            "http://User@example.com/Path/To?Q=Mixed#Frag"
+        );
+        assert_eq!(
+            normalize_url("https://Example.com/?next=/"),
+            "https://example.com/?next=/"
+        );
+    }
+
+    #[test]
+    fn yaml_config_round_trips() {
+        let dir = temp_dir("yaml_config_round_trips");
+        let path = dir.join("config.yaml");
+        fs::write(
+            &path,
+            "version: 1\n\
+             websites:\n\
+             - name: docs\n  \
+               url: https://docs.rs\n  \
+               tags: [rust, docs]\n  \
+               description: API docs\n",
+        )
+        .unwrap();
+
+        let mut config = Config::load(&path).unwrap();
+        assert_eq!(config.list()[0].tags(), ["rust", "docs"]);
+        assert_eq!(config.list()[0].description(), Some("API docs"));
+        config
+            .add(Website::new("crates", "https://crates.io"))
+            .unwrap();
+
+        let reloaded = Config::load(&path).unwrap();
+        assert_eq!(names(&reloaded), ["docs", "crates"]);
+        assert_eq!(reloaded.list()[0].tags(), ["rust", "docs"]);
+        assert_eq!(reloaded.list()[0].description(), Some("API docs"));
+    }
+
+    #[test]
+    fn picking_an_item_with_an_icon_resolves_its_website() {
+        let dir = temp_dir("picking_an_item_with_an_icon_resolves_its_website");
+        let mut config = config_with(&dir, &[("gitlab", "https://gitlab.com")]);
+        config
+            .add(Website {
+                icon: Some(String::from("🐙")),
+                description: Some(String::from("code")),
+                aliases: vec![String::from("gh")],
+                ..Website::new("github", "https://github.com")
+            })
+            .unwrap();
+
+        let item = PickerItem::new(&config.list()[1]);
+
+        assert_eq!(item.text(), "🐙 github (code) [gh]");
+        assert_eq!(item.output(), "github");
+        assert_eq!(
+            config.find(&item.output()).map(Website::url),
+            Some("https://github.com")
+        );
+    }
+
+    #[test]
+    fn import_skips_names_taken_ignoring_case_or_by_aliases() {
+        let dir = temp_dir("import_skips_names_taken_ignoring_case_or_by_aliases");
+        let mut config = config_with(&dir, &[]);
+        config
+            .add(Website {
+                aliases: vec![String::from("jira")],
+                ..Website::new("github", "https://github.com")
+            })
+            .unwrap();
+
+        let outcome = config
+            .merge_websites(numbered(
+                "website",
+                vec![
+                    Website::new("GitHub", "https://github.com"),
+                    Website::new("JIRA", "https://jira.example.com"),
+                    Website::new("docs", "https://docs.rs"),
+                ],
+            ))
+            .unwrap();
+
+        assert!(matches!(
+            outcome,
+            Outcome::Imported {
+                added: 1,
+                skipped: 2
+            }
+        ));
+        assert_eq!(names(&config), ["github", "docs"]);
+        assert_eq!(config.resolve("GITHUB").unwrap(), 0);
+    }
+
+    #[test]
+    fn per_website_flags_need_a_single_add() {
+        let matches = |args: &[&str]| {
+            cli()
+                .try_get_matches_from(std::iter::once("fzweb").chain(args.iter().copied()))
+                .unwrap()
+        };
+
+        let several = matches(&["--add", "a", "ua", "--add", "b", "ub", "--alias", "al"]);
+        let error = check_add_flags(&several).unwrap_err();
+        assert_eq!(error.kind(), clap::error::ErrorKind::ArgumentConflict);
+
+        assert!(check_add_flags(&matches(&["--add", "a", "ua", "--alias", "al"])).is_ok());
+        assert!(check_add_flags(&matches(&["--add", "a", "ua", "--add", "b", "ub"])).is_ok());
+        assert!(check_add_flags(&matches(&["--open", "--tag", "work"])).is_ok());
+    }
+
+    #[test]
+    fn run_reports_each_pair_and_skips_bad_ones() {
+        let dir = temp_dir("run_reports_each_pair_and_skips_bad_ones");
+        config_with(&dir, &[("taken", "https://taken.com")]);
+        let path = dir.join("config.json");
+        let add = [
+            "a",
+            "https://a.com",
+            "Taken",
+            "https://other.com",
+            "bad",
+            "not a url",
+            "b",
+            "https://b.com",
+        ];
+
+        let args = Args {
+            add: Some(add.iter().map(ToString::to_string).collect()),
+            ..args(&path)
+        };
+        run_with(args, &FakeSelector { pick: None }).unwrap();
+
+        assert_eq!(names(&Config::load(&path).unwrap()), ["taken", "a", "b"]);
+    }
+
+    #[test]
+    fn import_checks_websites_like_add() {
+        let dir = temp_dir("import_checks_websites_like_add");
+        let mut config = config_with(&dir, &[("a", "https://a.com")]);
+        let imported: Config = r#"{"websites": [
+            {"name": "", "url": "https://empty-name.com"},
+            {"name": "garbage", "url": "not a url"},
+            {"name": "blank", "url": "   "},
+            {"name": "  b  ", "url": " Example.com/Path "}
+        ]}"#
+        .parse()
+        .unwrap();
+
+        let outcome = config.merge(imported).unwrap();
+
+        assert!(matches!(
+            outcome,
+            Outcome::Imported {
+                added: 1,
+                skipped: 0
+            }
+        ));
+        let reloaded = Config::load(&dir.join("config.json")).unwrap();
+        assert_eq!(names(&reloaded), ["a", "b"]);
+        assert_eq!(reloaded.list()[1].url(), "https://example.com/Path");
+        assert!(reloaded.check_urls(true).is_ok());
+    }
+
+    #[test]
+    fn a_locked_config_rejects_other_writers() {
+        let dir = temp_dir("a_locked_config_rejects_other_writers");
+        let path = &dir.join("config.json");
+        config_with(&dir, &[("a", "https://a.com")]);
+
+        let (locked_tx, locked_rx) = std::sync::mpsc::channel();
+        let (done_tx, done_rx) = std::sync::mpsc::channel();
+        thread::scope(|scope| {
+            scope.spawn(move || {
+                let mut config = Config::load_locked(path).unwrap();
+                locked_tx.send(()).unwrap();
+                done_rx.recv().unwrap();
+                config.add(Website::new("b", "https://b.com")).unwrap();
+            });
+            scope.spawn(move || {
+                locked_rx.recv().unwrap();
+                let mut config = Config::load(path).unwrap();
+                let result = config.add(Website::new("c", "https://c.com"));
+                assert!(matches!(result, Err(FzwebError::Locked(_))));
+                assert!(matches!(
+                    Config::load_locked(path),
+                    Err(FzwebError::Locked(_))
+                ));
+                done_tx.send(()).unwrap();
+            });
+        });
+
+        // the first writer's change survived, and its lock file is gone
+        assert_eq!(names(&Config::load(path).unwrap()), ["a", "b"]);
+        assert!(!dir.join("config.json.lock").exists());
+        let mut config = Config::load_locked(path).unwrap();
+        config.add(Website::new("c", "https://c.com")).unwrap();
+        drop(config);
+        assert_eq!(names(&Config::load(path).unwrap()), ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn counting_an_opening_keeps_changes_saved_meanwhile() {
+        let dir = temp_dir("counting_an_opening_keeps_changes_saved_meanwhile");
+        let path = dir.join("config.json");
+        let mut picking = config_with(&dir, &[("a", "https://a.com")]);
+
+        let mut other = Config::load_locked(&path).unwrap();
+        other.add(Website::new("b", "https://b.com")).unwrap();
+        drop(other);
+        picking.record_open(0).unwrap();
+
+        let reloaded = Config::load(&path).unwrap();
+        assert_eq!(names(&reloaded), ["a", "b"]);
+        assert_eq!(reloaded.list()[0].open_count, 1);
+    }
+
+    #[test]
+    fn canonical_key_ignores_www_and_scheme() {
+        let key = canonical_key("https://example.com/docs");
+        for url in [
+            "http://example.com/docs",
+            "https://www.example.com/docs",
+            "HTTP://WWW.Example.com/docs/",
+            "www.example.com/docs//",
+        ] {
+            assert_eq!(canonical_key(url), key, "{}", url);
+        }
+        assert_ne!(canonical_key("https://example.com/Docs"), key);
+    }
+
+    #[test]
+    fn canonical_key_keeps_slashes_after_the_path() {
+        assert_eq!(
+            canonical_key("https://example.com/login/?next=/"),
+            "example.com/login?next=/"
+        );
+        assert_eq!(canonical_key("https://example.com/#/"), "example.com#/");
+        assert_ne!(
+            canonical_key("https://example.com/?next=/"),
+            canonical_key("https://example.com/?next=")
+        );
+    }
+
+    #[test]
+    fn names_differing_only_in_case_are_ambiguous() {
+        let dir = temp_dir("names_differing_only_in_case_are_ambiguous");
+        let path = dir.join("config.json");
+        fs::write(
+            &path,
+            r#"{"version": 1, "websites": [
+                {"name": "Docs", "url": "https://docs.rs"},
+                {"name": "docs", "url": "https://docs.python.org"}
+            ]}"#,
+        )
+        .unwrap();
+        let before = fs::read_to_string(&path).unwrap();
+
+        let mut config = Config::load(&path).unwrap();
+        config.dedupe().unwrap();
+        assert_eq!(names(&config), ["Docs", "docs"]);
+        assert_eq!(fs::read_to_string(&path).unwrap(), before);
+
+        let ambiguous = |result: MyResult<()>| {
+            matches!(
+                result,
+                Err(FzwebError::Ambiguous { name, candidates })
+                    if name == "DOCS" && candidates == ["Docs", "docs"]
+            )
+        };
+        assert!(ambiguous(config.resolve("DOCS").map(|_| ())));
+        assert!(config.find("DOCS").is_none());
+        assert!(ambiguous(config.remove("DOCS").map(|_| ())));
+        assert_eq!(names(&config), ["Docs", "docs"]);
+
+        // an exact match still singles one out
+        assert_eq!(config.resolve("docs").unwrap(), 1);
+    }
+
+    #[test]
+    fn parse_errors_are_prefixed_once() {
+        let dir = temp_dir("parse_errors_are_prefixed_once");
+        let path = dir.join("config.json");
+        fs::write(&path, "{").unwrap();
+
+        let message = Config::load(&path).unwrap_err().to_string();
+
+        assert!(message.starts_with("Failed to parse config file "));
+        assert!(!message.contains("Failed to parse config:"), "{}", message);
+    }
+
+    #[test]
+    fn rename_trims_and_rejects_empty_names() {
+        let dir = temp_dir("rename_trims_and_rejects_empty_names");
+        let mut config = config_with(&dir, &[("a", "https://a.com")]);
+
+        for new in ["", "   "] {
+            let result = config.rename_website(String::from("a"), String::from(new));
+            assert!(matches!(result, Err(FzwebError::EmptyField("name"))));
+        }
+        assert_eq!(names(&config), ["a"]);
+
+        config
+            .rename_website(String::from("a"), String::from("  spaced  "))
+            .unwrap();
+        assert_eq!(names(&config), ["spaced"]);
+    }
+
+    #[test]
+    fn add_from_reader_backs_up_and_adds_valid_lines() {
+        let dir = temp_dir("add_from_reader_backs_up_and_adds_valid_lines");
+        let mut config = config_with(&dir, &[("a", "https://a.com")]);
+        let before = fs::read_to_string(dir.join("config.json")).unwrap();
+        let input =
+            "b\thttps://b.com\nmalformed\n\nA,https://other.com\nbad,not a url\n c , c.com \n";
+
+        let outcome = config.add_from_reader(input.as_bytes(), false).unwrap();
+
+        assert!(matches!(
+            outcome,
+            Outcome::Imported {
+                added: 2,
+                skipped: 1
+            }
+        ));
+        let reloaded = Config::load(&dir.join("config.json")).unwrap();
+        assert_eq!(names(&reloaded), ["a", "b", "c"]);
+        assert_eq!(reloaded.list()[2].url(), "https://c.com");
+        assert_eq!(
+            fs::read_to_string(dir.join("config.json.bak")).unwrap(),
+            before
+        );
+    }
+
+    #[test]
+    fn creating_after_picking_keeps_changes_saved_meanwhile() {
+        let dir = temp_dir("creating_after_picking_keeps_changes_saved_meanwhile");
+        let path = dir.join("config.json");
+        let mut picking = config_with(&dir, &[("a", "https://a.com")]);
+
+        let mut other = Config::load_locked(&path).unwrap();
+        other.add(Website::new("b", "https://b.com")).unwrap();
+        drop(other);
+        picking.reload_locked().unwrap();
+        picking.add(Website::new("c", "https://c.com")).unwrap();
+
+        assert!(matches!(
+            Config::load_locked(&path),
+            Err(FzwebError::Locked(_))
+        ));
+        drop(picking);
+        assert_eq!(names(&Config::load(&path).unwrap()), ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn unset_website_fields_are_left_out_of_the_file() {
+        let json = serde_json::to_value(Website::new("a", "https://a.com")).unwrap();
+        let fields: Vec<&str> = json
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect();
+        assert_eq!(fields, ["last_opened", "name", "open_count", "url"]);
+    }
+
+    #[test]
+    fn add_rejects_blank_names_and_urls_without_saving() {
+        let dir = temp_dir("add_rejects_blank_names_and_urls_without_saving");
+        let mut config = config_with(&dir, &[("a", "https://a.com")]);
+        let before = fs::read_to_string(dir.join("config.json")).unwrap();
+
+        for name in ["", "   "] {
+            let result = config.add(Website::new(name, "https://b.com"));
+            assert!(matches!(result, Err(FzwebError::EmptyField("name"))));
+        }
+        let result = config.add(Website::new("b", "   "));
+        assert!(matches!(result, Err(FzwebError::EmptyField("URL"))));
+
+        assert_eq!(names(&config), ["a"]);
+        assert_eq!(fs::read_to_string(dir.join("config.json")).unwrap(), before);
+    }
 }